@@ -1,6 +1,5 @@
 use cosmwasm_schema::write_api;
-use cosmwasm_std::Empty;
-use mars_delegator::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg};
+use mars_delegator::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 
 fn main() {
     write_api! {
@@ -8,6 +7,6 @@ fn main() {
         sudo: SudoMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
-        migrate: Empty,
+        migrate: MigrateMsg,
     }
 }
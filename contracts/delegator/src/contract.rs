@@ -1,13 +1,12 @@
 use cosmwasm_std::{
-    entry_point, to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response,
-    StdResult,
+    entry_point, to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
 };
 
 use crate::{
     error::ContractError,
     execute, migrations,
-    msg::{ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg},
-    query,
+    msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg},
+    program, query,
     types::MarsMsg,
 };
 
@@ -17,19 +16,57 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    execute::init(deps, msg)
+    execute::init(deps, env, msg)
 }
 
 #[entry_point]
 pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response<MarsMsg>, ContractError> {
     match msg {
-        SudoMsg::Bond {} => execute::bond(deps, env),
-        SudoMsg::ForceUnbond {} => execute::force_unbond(deps, env),
+        SudoMsg::Bond {
+            program: None,
+        } => execute::bond(deps, env),
+        SudoMsg::Bond {
+            program: Some(id),
+        } => program::bond(deps, env, id),
+        SudoMsg::ForceUnbond {
+            program: None,
+        } => execute::force_unbond(deps, env),
+        SudoMsg::ForceUnbond {
+            program: Some(id),
+        } => program::unbond(deps, env, id, true),
+        SudoMsg::UpdateConfig {
+            bond_denom,
+            ending_time,
+            min_validators,
+            reward_policy,
+            unbonding_period,
+        } => execute::update_config(
+            deps,
+            env,
+            bond_denom,
+            ending_time,
+            min_validators,
+            reward_policy,
+            unbonding_period,
+        ),
+        SudoMsg::CreateProgram {
+            id,
+            program,
+        } => program::create_program(deps, env, id, program),
+        SudoMsg::CancelProgram {
+            id,
+        } => program::cancel_program(deps, env, id),
+        SudoMsg::UpdateValidators {
+            validators,
+        } => execute::update_validators(deps, validators),
+        SudoMsg::SetRewardWithdrawAddress {
+            address,
+        } => execute::set_reward_withdraw_address(deps, env, address),
     }
 }
 
@@ -41,19 +78,58 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response<MarsMsg>, ContractError> {
     match msg {
-        ExecuteMsg::Unbond {} => execute::unbond(deps, env),
-        ExecuteMsg::Refund {} => execute::refund(deps, env),
+        ExecuteMsg::Unbond {
+            program: None,
+        } => execute::unbond(deps, env),
+        ExecuteMsg::Unbond {
+            program: Some(id),
+        } => program::unbond(deps, env, id, false),
+        ExecuteMsg::Refund {
+            program: None,
+        } => execute::refund(deps, env),
+        ExecuteMsg::Refund {
+            program: Some(id),
+        } => program::refund(deps, env, id),
+        ExecuteMsg::Rebalance {} => execute::rebalance(deps, env),
+        ExecuteMsg::Harvest {} => execute::harvest(deps, env),
     }
 }
 
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_json_binary(&query::query_config(deps)?),
+        QueryMsg::Rewards {} => to_json_binary(&query::query_rewards(deps)?),
+        QueryMsg::Status {} => to_json_binary(&query::query_status(deps, env)?),
+        QueryMsg::Delegations {} => to_json_binary(&query::query_delegations(deps, env)?),
+        QueryMsg::Balances {} => to_json_binary(&query::query_balances(deps, env)?),
+        QueryMsg::Phase {} => to_json_binary(&query::query_phase(deps, env)?),
+        QueryMsg::Program {
+            id,
+        } => to_json_binary(&program::query_program(deps, env, id)?),
+        QueryMsg::Programs {
+            start_after,
+            start_before,
+            limit,
+            order,
+        } => to_json_binary(&program::query_programs(
+            deps,
+            env,
+            start_after,
+            start_before,
+            limit,
+            order,
+        )?),
+        QueryMsg::History {
+            start_after,
+            start_before,
+            limit,
+            order,
+        } => to_json_binary(&query::query_history(deps, start_after, start_before, limit, order)?),
     }
 }
 
 #[entry_point]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: Empty) -> Result<Response, ContractError> {
-    migrations::v1_1::migrate(deps)
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    migrations::redelegate::migrate(deps, env, msg)
 }
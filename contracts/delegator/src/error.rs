@@ -1,6 +1,8 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
+use crate::msg::Phase;
+
 #[derive(Debug, Error, PartialEq)]
 pub enum ContractError {
     #[error(transparent)]
@@ -12,12 +14,94 @@ pub enum ContractError {
     #[error("contract does not hold any coin to be refunded")]
     NothingToRefund,
 
+    #[error("there are no staking rewards to be harvested")]
+    NothingToHarvest,
+
+    #[error("delegations already match the target allocation, or can't be redelegated yet")]
+    NothingToRebalance,
+
+    #[error("not enough active validators to delegate to: found {found}, required {required}")]
+    NoValidators {
+        found: usize,
+        required: usize,
+    },
+
+    #[error("ending time must be in the future! ending: {ending_time}, current: {current_time}")]
+    InvalidEndingTime {
+        ending_time: u64,
+        current_time: u64,
+    },
+
+    #[error("invalid denom {denom}: {reason}")]
+    InvalidDenom {
+        denom: String,
+        reason: String,
+    },
+
+    #[error("bond denom cannot be changed while tokens are bonded or unbonding")]
+    BondDenomLocked,
+
+    #[error("validator {address} is not in the active set")]
+    ValidatorNotFound {
+        address: String,
+    },
+
+    #[error("validator {address} is listed more than once")]
+    DuplicateValidator {
+        address: String,
+    },
+
+    #[error("weight of validator {address} must be greater than zero")]
+    ZeroWeight {
+        address: String,
+    },
+
+    #[error("invalid redelegation from {src} to {dst}: {reason}")]
+    InvalidRedelegation {
+        src: String,
+        dst: String,
+        reason: String,
+    },
+
+    #[error("cannot redelegate {requested} from validator {validator}: only {available} can be redelegated")]
+    InsufficientRedelegatable {
+        validator: String,
+        requested: u128,
+        available: u128,
+    },
+
     #[error("ending time is not reached yet! ending: {ending_time}, current: {current_time}")]
     EndingTimeNotReached {
         ending_time: u64,
         current_time: u64,
     },
 
+    #[error("program {id} already exists")]
+    ProgramExists {
+        id: String,
+    },
+
+    #[error("program {id} cannot do this while in phase {phase}")]
+    InvalidProgramPhase {
+        id: String,
+        phase: String,
+    },
+
+    #[error("insufficient funds for program {id}: required {required}, available {available}")]
+    InsufficientFunds {
+        id: String,
+        required: u128,
+        available: u128,
+    },
+
+    #[error(
+        "unbonding is not complete yet! completion: {completion_time}, current: {current_time}"
+    )]
+    UnbondingNotComplete {
+        completion_time: u64,
+        current_time: u64,
+    },
+
     #[error("incorrect contract: expecting {expect}, found {found}")]
     IncorrectContract {
         expect: String,
@@ -39,6 +123,97 @@ impl ContractError {
         }
     }
 
+    pub fn no_validators(found: usize, required: usize) -> Self {
+        Self::NoValidators {
+            found,
+            required,
+        }
+    }
+
+    pub fn invalid_ending_time(ending_time: u64, current_time: u64) -> Self {
+        Self::InvalidEndingTime {
+            ending_time,
+            current_time,
+        }
+    }
+
+    pub fn invalid_denom(denom: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::InvalidDenom {
+            denom: denom.into(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn validator_not_found(address: impl Into<String>) -> Self {
+        Self::ValidatorNotFound {
+            address: address.into(),
+        }
+    }
+
+    pub fn duplicate_validator(address: impl Into<String>) -> Self {
+        Self::DuplicateValidator {
+            address: address.into(),
+        }
+    }
+
+    pub fn zero_weight(address: impl Into<String>) -> Self {
+        Self::ZeroWeight {
+            address: address.into(),
+        }
+    }
+
+    pub fn invalid_redelegation(
+        src: impl Into<String>,
+        dst: impl Into<String>,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self::InvalidRedelegation {
+            src: src.into(),
+            dst: dst.into(),
+            reason: reason.into(),
+        }
+    }
+
+    pub fn insufficient_redelegatable(
+        validator: impl Into<String>,
+        requested: u128,
+        available: u128,
+    ) -> Self {
+        Self::InsufficientRedelegatable {
+            validator: validator.into(),
+            requested,
+            available,
+        }
+    }
+
+    pub fn program_exists(id: impl Into<String>) -> Self {
+        Self::ProgramExists {
+            id: id.into(),
+        }
+    }
+
+    pub fn invalid_program_phase(id: impl Into<String>, phase: &Phase) -> Self {
+        Self::InvalidProgramPhase {
+            id: id.into(),
+            phase: format!("{phase:?}").to_lowercase(),
+        }
+    }
+
+    pub fn insufficient_funds(id: impl Into<String>, required: u128, available: u128) -> Self {
+        Self::InsufficientFunds {
+            id: id.into(),
+            required,
+            available,
+        }
+    }
+
+    pub fn unbonding_not_complete(completion_time: u64, current_time: u64) -> Self {
+        Self::UnbondingNotComplete {
+            completion_time,
+            current_time,
+        }
+    }
+
     pub fn incorrect_contract(expect: impl Into<String>, found: impl Into<String>) -> Self {
         Self::IncorrectContract {
             expect: expect.into(),
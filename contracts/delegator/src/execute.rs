@@ -1,25 +1,67 @@
-use cosmwasm_std::{coin, Addr, DepsMut, Env, QuerierWrapper, Response, StakingMsg, StdResult};
+use std::collections::HashSet;
 
-use crate::{error::ContractError, msg::Config, state::CONFIG, types::MarsMsg};
+use cosmwasm_std::{
+    coin, coins, Addr, BankMsg, Coin, Coins, DepsMut, DistributionMsg, Env, Order, QuerierWrapper,
+    Response, StakingMsg, StdResult, Storage, Uint128,
+};
+
+use crate::{
+    error::ContractError,
+    msg::{Action, Config, HistoryEntry, Phase, RewardPolicy, UnbondingBatch, WeightedValidator},
+    program::{main_delegations, record_shares, reserved_liquid},
+    state::{
+        CONFIG, HARVESTED, HISTORY, HISTORY_COUNT, PROGRAM_PHASES, UNBONDING_BATCHES,
+        UNBONDING_BATCH_COUNT,
+    },
+    types::MarsMsg,
+};
+
+pub fn init(deps: DepsMut, env: Env, cfg: Config) -> Result<Response, ContractError> {
+    validate_ending_time(&env, cfg.ending_time)?;
+    validate_denom(&cfg.bond_denom)?;
+    validate_validators(&deps.querier, &cfg.validators)?;
+
+    if let RewardPolicy::Send {
+        recipient,
+    } = &cfg.reward_policy
+    {
+        deps.api.addr_validate(recipient)?;
+    }
+
+    let mut res = Response::new();
+    if let Some(address) = &cfg.reward_withdraw_address {
+        res = res.add_message(DistributionMsg::SetWithdrawAddress {
+            address: deps.api.addr_validate(address)?.into(),
+        });
+    }
 
-pub fn init(deps: DepsMut, cfg: Config) -> Result<Response, ContractError> {
-    // We don't implement a validity check of the ending time.
-    // The deployer must make sure to provide a valid value.
     CONFIG.save(deps.storage, &cfg)?;
 
-    Ok(Response::new())
+    Ok(res)
 }
 
 pub fn bond(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError> {
     let cfg = CONFIG.load(deps.storage)?;
 
-    let amount = deps.querier.query_balance(env.contract.address, &cfg.bond_denom)?.amount;
+    // tokens set aside for named programs are not bonded by the main program
+    let balance = deps.querier.query_balance(&env.contract.address, &cfg.bond_denom)?.amount;
+    let reserved = reserved_liquid(deps.storage, env.block.time.seconds(), None)?;
+    let amount = balance.saturating_sub(reserved);
 
     if amount.is_zero() {
         return Err(ContractError::NothingToBond);
     }
 
-    let msgs = get_delegation_msgs(&deps.querier, amount.u128(), &cfg.bond_denom)?;
+    let msgs = get_delegation_msgs(
+        &deps.querier,
+        &cfg.validators,
+        cfg.min_validators,
+        amount.u128(),
+        &cfg.bond_denom,
+    )?;
+
+    record_shares(&deps.querier, deps.storage, &env.contract.address, None, &msgs)?;
+    record_history(deps.storage, &env, None, Action::Bond, coins(amount.u128(), &cfg.bond_denom))?;
 
     Ok(Response::new()
         .add_messages(msgs)
@@ -28,10 +70,16 @@ pub fn bond(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError>
 }
 
 pub fn force_unbond(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError> {
-    let msgs = get_undelegate_msgs(&deps.querier, &env.contract.address)?;
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let msgs = get_undelegate_msgs(&deps.querier, deps.storage, &env.contract.address, &cfg)?;
+    record_shares(&deps.querier, deps.storage, &env.contract.address, None, &msgs)?;
+    let completion_time = record_unbonding(deps, &env, &cfg, None, &msgs, Action::ForceUnbond)?;
+
     Ok(Response::new()
         .add_messages(msgs)
-        .add_attribute("action", "periphery/delegator/force_unbond"))
+        .add_attribute("action", "periphery/delegator/force_unbond")
+        .add_attribute("completion_time", completion_time.to_string()))
 }
 
 pub fn unbond(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError> {
@@ -42,18 +90,329 @@ pub fn unbond(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractErro
         return Err(ContractError::ending_time_not_reached(cfg.ending_time, current_time));
     }
 
-    let msgs = get_undelegate_msgs(&deps.querier, &env.contract.address)?;
+    let msgs = get_undelegate_msgs(&deps.querier, deps.storage, &env.contract.address, &cfg)?;
+    record_shares(&deps.querier, deps.storage, &env.contract.address, None, &msgs)?;
+    let completion_time = record_unbonding(deps, &env, &cfg, None, &msgs, Action::Unbond)?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "periphery/delegator/unbond")
+        .add_attribute("completion_time", completion_time.to_string()))
+}
+
+/// Record the undelegations as an unbonding batch of the given program, and return the time they
+/// will complete.
+pub(crate) fn record_unbonding(
+    deps: DepsMut,
+    env: &Env,
+    cfg: &Config,
+    program: Option<&str>,
+    msgs: &[StakingMsg],
+    action: Action,
+) -> Result<u64, ContractError> {
+    let completion_time = env.block.time.seconds() + cfg.unbonding_period;
+
+    let amount = msgs
+        .iter()
+        .map(|msg| match msg {
+            StakingMsg::Undelegate {
+                amount,
+                ..
+            } => amount.amount,
+            _ => Uint128::zero(),
+        })
+        .sum::<Uint128>();
+
+    // nothing was delegated, so nothing will be returned
+    if amount.is_zero() {
+        return Ok(completion_time);
+    }
+
+    let id = UNBONDING_BATCH_COUNT.may_load(deps.storage)?.unwrap_or(0) + 1;
+    UNBONDING_BATCH_COUNT.save(deps.storage, &id)?;
+
+    UNBONDING_BATCHES.save(
+        deps.storage,
+        id,
+        &UnbondingBatch {
+            id,
+            program: program.map(Into::into),
+            amount,
+            completion_time,
+        },
+    )?;
+
+    record_history(deps.storage, env, program, action, coins(amount.u128(), &cfg.bond_denom))?;
+
+    Ok(completion_time)
+}
+
+pub(crate) fn record_history(
+    storage: &mut dyn Storage,
+    env: &Env,
+    program: Option<&str>,
+    action: Action,
+    amount: Vec<Coin>,
+) -> StdResult<()> {
+    let id = HISTORY_COUNT.may_load(storage)?.unwrap_or(0) + 1;
+    HISTORY_COUNT.save(storage, &id)?;
+
+    HISTORY.save(
+        storage,
+        id,
+        &HistoryEntry {
+            id,
+            program: program.map(Into::into),
+            action,
+            amount,
+            timestamp: env.block.time.seconds(),
+        },
+    )
+}
+
+pub fn rebalance(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let msgs = get_redelegate_msgs(&deps.querier, deps.storage, &env.contract.address, &cfg)?;
+
+    if msgs.is_empty() {
+        return Err(ContractError::NothingToRebalance);
+    }
+
+    record_shares(&deps.querier, deps.storage, &env.contract.address, None, &msgs)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "periphery/delegator/rebalance")
+        .add_attribute("num_redelegations", msgs.len().to_string())
+        .add_messages(msgs))
+}
+
+pub fn harvest(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // sum up the rewards to be claimed, so that they can be handled in the same transaction
+    let mut rewards = Coins::default();
+    let mut withdraw_msgs = vec![];
+    for delegation in deps.querier.query_all_delegations(&env.contract.address)? {
+        let Some(delegation) =
+            deps.querier.query_delegation(&env.contract.address, delegation.validator)?
+        else {
+            continue;
+        };
+
+        if delegation.accumulated_rewards.iter().all(|coin| coin.amount.is_zero()) {
+            continue;
+        }
+
+        for coin in delegation.accumulated_rewards {
+            rewards.add(coin)?;
+        }
+
+        withdraw_msgs.push(DistributionMsg::WithdrawDelegatorReward {
+            validator: delegation.validator,
+        });
+    }
+
+    if rewards.is_empty() {
+        return Err(ContractError::NothingToHarvest);
+    }
+
+    for coin in rewards.iter() {
+        HARVESTED.update(deps.storage, &coin.denom, |total| -> StdResult<_> {
+            Ok(total.unwrap_or_default() + coin.amount)
+        })?;
+    }
+
+    let res = Response::new()
+        .add_messages(withdraw_msgs)
+        .add_attribute("action", "periphery/delegator/harvest")
+        .add_attribute("rewards", rewards.to_string());
+
+    // rewards are paid out to another address, so there is nothing left for the contract to do
+    if cfg.reward_withdraw_address.is_some() {
+        return Ok(res);
+    }
+
+    let res = match cfg.reward_policy {
+        RewardPolicy::Restake => {
+            let amount = rewards.amount_of(&cfg.bond_denom);
+            if amount.is_zero() {
+                res
+            } else {
+                let msgs = get_delegation_msgs(
+                    &deps.querier,
+                    &cfg.validators,
+                    cfg.min_validators,
+                    amount.u128(),
+                    &cfg.bond_denom,
+                )?;
+                record_shares(&deps.querier, deps.storage, &env.contract.address, None, &msgs)?;
+                res.add_messages(msgs)
+            }
+        }
+        RewardPolicy::CommunityPool => res.add_message(MarsMsg::FundCommunityPool {
+            amount: rewards.into_vec(),
+        }),
+        RewardPolicy::Send {
+            recipient,
+        } => res.add_message(BankMsg::Send {
+            to_address: recipient,
+            amount: rewards.into_vec(),
+        }),
+    };
+
+    Ok(res)
+}
+
+pub fn set_reward_withdraw_address(
+    deps: DepsMut,
+    env: Env,
+    address: Option<String>,
+) -> Result<Response<MarsMsg>, ContractError> {
+    let withdraw_addr = match &address {
+        Some(address) => deps.api.addr_validate(address)?,
+        None => env.contract.address,
+    };
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.reward_withdraw_address = address;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_message(DistributionMsg::SetWithdrawAddress {
+            address: withdraw_addr.to_string(),
+        })
+        .add_attribute("action", "periphery/delegator/set_reward_withdraw_address")
+        .add_attribute("address", withdraw_addr))
+}
+
+pub fn update_config(
+    deps: DepsMut,
+    env: Env,
+    bond_denom: Option<String>,
+    ending_time: Option<u64>,
+    min_validators: Option<u32>,
+    reward_policy: Option<RewardPolicy>,
+    unbonding_period: Option<u64>,
+) -> Result<Response<MarsMsg>, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+
+    if let Some(bond_denom) = bond_denom {
+        validate_denom(&bond_denom)?;
+
+        // accounting of bonded and unbonding tokens is in the current denom
+        if bond_denom != cfg.bond_denom && is_bonded(&deps, &env)? {
+            return Err(ContractError::BondDenomLocked);
+        }
+
+        cfg.bond_denom = bond_denom;
+    }
+
+    if let Some(ending_time) = ending_time {
+        validate_ending_time(&env, ending_time)?;
+        cfg.ending_time = ending_time;
+    }
+
+    if let Some(min_validators) = min_validators {
+        cfg.min_validators = min_validators;
+    }
+
+    if let Some(reward_policy) = reward_policy {
+        if let RewardPolicy::Send {
+            recipient,
+        } = &reward_policy
+        {
+            deps.api.addr_validate(recipient)?;
+        }
+        cfg.reward_policy = reward_policy;
+    }
+
+    if let Some(unbonding_period) = unbonding_period {
+        cfg.unbonding_period = unbonding_period;
+    }
+
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new().add_attribute("action", "periphery/delegator/update_config"))
+}
+
+/// Whether any tokens are delegated, or undelegated but not yet refunded, by any program, or set
+/// aside as the budget of a named program that hasn't been bonded yet.
+fn is_bonded(deps: &DepsMut, env: &Env) -> StdResult<bool> {
+    if !deps.querier.query_all_delegations(&env.contract.address)?.is_empty() {
+        return Ok(true);
+    }
+
+    if !UNBONDING_BATCHES.is_empty(deps.storage) {
+        return Ok(true);
+    }
+
+    for res in PROGRAM_PHASES.range(deps.storage, None, None, Order::Ascending) {
+        let (_, phase) = res?;
+        if !matches!(phase, Phase::Refunded) {
+            return Ok(true);
+        }
+    }
 
-    Ok(Response::new().add_messages(msgs).add_attribute("action", "periphery/delegator/unbond"))
+    Ok(false)
+}
+
+pub fn update_validators(
+    deps: DepsMut,
+    validators: Vec<WeightedValidator>,
+) -> Result<Response<MarsMsg>, ContractError> {
+    validate_validators(&deps.querier, &validators)?;
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.validators = validators;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "periphery/delegator/update_validators")
+        .add_attribute("num_validators", cfg.validators.len().to_string()))
 }
 
 pub fn refund(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractError> {
-    let amount = deps.querier.query_all_balances(env.contract.address)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    let current_time = env.block.time.seconds();
+
+    // wait for all unbondings of the main program to complete, so that everything is refunded at
+    // once
+    let batches = UNBONDING_BATCHES
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|res| !matches!(res, Ok((_, batch)) if batch.program.is_some()))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    if let Some(completion_time) = batches.iter().map(|(_, batch)| batch.completion_time).max() {
+        if current_time < completion_time {
+            return Err(ContractError::unbonding_not_complete(completion_time, current_time));
+        }
+    }
+
+    for (id, _) in batches {
+        UNBONDING_BATCHES.remove(deps.storage, id);
+    }
+
+    // tokens set aside for named programs are refunded by those programs
+    let reserved = reserved_liquid(deps.storage, current_time, None)?;
+    let amount = deps
+        .querier
+        .query_all_balances(&env.contract.address)?
+        .into_iter()
+        .filter_map(|mut coin| {
+            if coin.denom == cfg.bond_denom {
+                coin.amount = coin.amount.saturating_sub(reserved);
+            }
+            (!coin.amount.is_zero()).then_some(coin)
+        })
+        .collect::<Vec<_>>();
 
     if amount.is_empty() {
         return Err(ContractError::NothingToRefund);
     }
 
+    record_history(deps.storage, &env, None, Action::Refund, amount.clone())?;
+
     Ok(Response::new()
         .add_message(MarsMsg::FundCommunityPool {
             amount,
@@ -61,49 +420,228 @@ pub fn refund(deps: DepsMut, env: Env) -> Result<Response<MarsMsg>, ContractErro
         .add_attribute("action", "periphery/delegator/refund"))
 }
 
-/// Query the validator set, and generate messages to delegate evenly to each validator.
+/// Generate messages to delegate to the given validators, in proportion to their weights. If no
+/// validator is given, delegate evenly to the entire active validator set.
+pub fn get_delegation_msgs(
+    querier: &QuerierWrapper,
+    validators: &[WeightedValidator],
+    min_validators: u32,
+    amount: u128,
+    denom: &str,
+) -> Result<Vec<StakingMsg>, ContractError> {
+    Ok(compute_allocation(querier, validators, min_validators, amount)?
+        .into_iter()
+        // delegating zero tokens would fail
+        .filter(|(_, tokens)| *tokens > 0)
+        .map(|(validator, tokens)| StakingMsg::Delegate {
+            validator,
+            amount: coin(tokens, denom),
+        })
+        .collect())
+}
+
+/// Split an amount among the given validators, in proportion to their weights. If no validator is
+/// given, split it evenly among the entire active validator set.
 ///
-/// Need to handle the case where the coin balance is not divisible by the number of validators.
+/// Only validators in the active set are eligible; configured validators that are jailed,
+/// tombstoned or otherwise unbonded are skipped, and their share goes to the others. Returns an
+/// error if fewer than `min_validators` (and at least one) validators remain.
+///
+/// Need to handle the case where the coin balance is not divisible by the total weight.
 /// For this we use the same algorithm from Steak:
 /// https://github.com/steak-enjoyers/steak/blob/v2.0.0-rc0/contracts/hub/src/math.rs#L52-L90
-///
-/// NOTE: We don't handle the case where the number of validators is zero, because it's impossible.
-pub fn get_delegation_msgs(
+/// Each validator first receives its share rounded down; the remainder, which is less than the
+/// number of validators, is then handed out one token at a time starting from the first validator.
+pub fn compute_allocation(
     querier: &QuerierWrapper,
+    validators: &[WeightedValidator],
+    min_validators: u32,
     amount: u128,
-    denom: &str,
-) -> StdResult<Vec<StakingMsg>> {
-    let validators = querier.query_all_validators()?;
-    let num_validators = validators.len() as u128;
+) -> Result<Vec<(String, u128)>, ContractError> {
+    // the staking module only returns validators in the active set
+    let active = querier.query_all_validators()?;
 
-    let tokens_per_validator = amount / num_validators;
-    let remainder = amount % num_validators;
+    let validators = if validators.is_empty() {
+        active
+            .into_iter()
+            .map(|validator| WeightedValidator {
+                address: validator.address,
+                weight: 1,
+            })
+            .collect::<Vec<_>>()
+    } else {
+        let active = active.into_iter().map(|v| v.address).collect::<HashSet<_>>();
+        validators.iter().filter(|v| active.contains(&v.address)).cloned().collect()
+    };
+
+    let required = min_validators.max(1) as usize;
+    if validators.len() < required {
+        return Err(ContractError::no_validators(validators.len(), required));
+    }
+
+    let total_weight: u128 = validators.iter().map(|v| u128::from(v.weight)).sum();
+
+    let shares = validators
+        .iter()
+        .map(|v| Uint128::new(amount).multiply_ratio(v.weight, total_weight).u128())
+        .collect::<Vec<_>>();
+    let remainder = amount - shares.iter().sum::<u128>();
 
     Ok(validators
         .into_iter()
+        .zip(shares)
         .enumerate()
-        .map(|(idx, validator)| {
+        .map(|(idx, (validator, share))| {
             let remainder_for_validator = u128::from((idx + 1) as u128 <= remainder);
-            let tokens_for_validator = tokens_per_validator + remainder_for_validator;
-            StakingMsg::Delegate {
-                validator: validator.address,
-                amount: coin(tokens_for_validator, denom),
-            }
+            (validator.address, share + remainder_for_validator)
         })
         .collect())
 }
 
-/// Query current delegations made to validators, and generate messages to undelegate from them.
+/// Compare current delegations of the main program with its target allocation, and generate
+/// messages to redelegate from validators holding more than their target to those holding less.
+///
+/// Only the amount each delegation can currently redelegate is moved. Tokens that were themselves
+/// redelegated recently can't be redelegated again until the unbonding period has passed, so such
+/// delegations are left for a later rebalance.
+pub fn get_redelegate_msgs(
+    querier: &QuerierWrapper,
+    storage: &dyn Storage,
+    delegator_addr: &Addr,
+    cfg: &Config,
+) -> Result<Vec<StakingMsg>, ContractError> {
+    let delegations = main_delegations(querier, storage, delegator_addr)?;
+    let total = delegations.iter().map(|(_, amount)| amount.u128()).sum();
+
+    let targets = compute_allocation(querier, &cfg.validators, cfg.min_validators, total)?;
+    let target_of = |validator: &str| {
+        targets.iter().find(|(v, _)| v == validator).map(|(_, tokens)| *tokens).unwrap_or(0)
+    };
+
+    // validators holding more than their target, and how much each can give away
+    let mut surpluses = vec![];
+    for (validator, amount) in &delegations {
+        let surplus = amount.u128().saturating_sub(target_of(validator));
+        if surplus == 0 {
+            continue;
+        }
+
+        let can_redelegate = querier
+            .query_delegation(delegator_addr, validator)?
+            .map(|d| d.can_redelegate.amount.u128())
+            .unwrap_or(0);
+
+        let movable = surplus.min(can_redelegate);
+        if movable > 0 {
+            surpluses.push((validator.clone(), movable));
+        }
+    }
+
+    // validators holding less than their target, and how much each is short of it
+    let mut deficits = targets
+        .iter()
+        .filter_map(|(validator, target)| {
+            let current = delegations
+                .iter()
+                .find(|(v, _)| v == validator)
+                .map(|(_, amount)| amount.u128())
+                .unwrap_or(0);
+            let deficit = target.saturating_sub(current);
+            (deficit > 0).then(|| (validator.clone(), deficit))
+        })
+        .collect::<Vec<_>>();
+
+    // match surpluses with deficits in order; each step exhausts one side or the other, so the
+    // number of messages is less than the number of validators involved
+    let mut msgs = vec![];
+    let mut deficit_idx = 0;
+    for (src_validator, mut surplus) in surpluses {
+        while surplus > 0 && deficit_idx < deficits.len() {
+            let (dst_validator, deficit) = &mut deficits[deficit_idx];
+            let tokens = surplus.min(*deficit);
+
+            msgs.push(StakingMsg::Redelegate {
+                src_validator: src_validator.clone(),
+                dst_validator: dst_validator.clone(),
+                amount: coin(tokens, &cfg.bond_denom),
+            });
+
+            surplus -= tokens;
+            *deficit -= tokens;
+            if *deficit == 0 {
+                deficit_idx += 1;
+            }
+        }
+    }
+
+    Ok(msgs)
+}
+
+pub(crate) fn validate_ending_time(env: &Env, ending_time: u64) -> Result<(), ContractError> {
+    let current_time = env.block.time.seconds();
+
+    if ending_time <= current_time {
+        return Err(ContractError::invalid_ending_time(ending_time, current_time));
+    }
+
+    Ok(())
+}
+
+/// Ensure the denom follows the Cosmos SDK format: 3 to 128 characters, starting with a letter,
+/// followed by letters, digits or any of `/:._-`.
+fn validate_denom(denom: &str) -> Result<(), ContractError> {
+    if denom.len() < 3 || denom.len() > 128 {
+        return Err(ContractError::invalid_denom(denom, "must be 3 to 128 characters long"));
+    }
+
+    let mut chars = denom.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_alphabetic()) {
+        return Err(ContractError::invalid_denom(denom, "must start with a letter"));
+    }
+
+    if !chars.all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c)) {
+        return Err(ContractError::invalid_denom(denom, "contains invalid characters"));
+    }
+
+    Ok(())
+}
+
+/// Ensure each validator is in the active set, is listed only once, and has a non-zero weight.
+pub(crate) fn validate_validators(
+    querier: &QuerierWrapper,
+    validators: &[WeightedValidator],
+) -> Result<(), ContractError> {
+    let mut seen = HashSet::new();
+
+    for validator in validators {
+        if validator.weight == 0 {
+            return Err(ContractError::zero_weight(&validator.address));
+        }
+
+        if !seen.insert(&validator.address) {
+            return Err(ContractError::duplicate_validator(&validator.address));
+        }
+
+        if querier.query_validator(&validator.address)?.is_none() {
+            return Err(ContractError::validator_not_found(&validator.address));
+        }
+    }
+
+    Ok(())
+}
+
+/// Query current delegations of the main program, and generate messages to undelegate from them.
 pub fn get_undelegate_msgs(
     querier: &QuerierWrapper,
+    storage: &dyn Storage,
     delegator_addr: &Addr,
+    cfg: &Config,
 ) -> StdResult<Vec<StakingMsg>> {
-    Ok(querier
-        .query_all_delegations(delegator_addr)?
+    Ok(main_delegations(querier, storage, delegator_addr)?
         .into_iter()
-        .map(|delegation| StakingMsg::Undelegate {
-            validator: delegation.validator,
-            amount: delegation.amount,
+        .map(|(validator, amount)| StakingMsg::Undelegate {
+            validator,
+            amount: coin(amount.u128(), &cfg.bond_denom),
         })
         .collect())
 }
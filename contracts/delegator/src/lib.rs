@@ -4,6 +4,7 @@ pub mod error;
 pub mod execute;
 pub mod migrations;
 pub mod msg;
+pub mod program;
 pub mod query;
 pub mod state;
 pub mod types;
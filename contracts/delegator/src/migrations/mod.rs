@@ -1 +1 @@
-pub mod v1_1;
+pub mod redelegate;
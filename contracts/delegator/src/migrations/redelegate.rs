@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use cosmwasm_std::{coin, DepsMut, Env, Response, StakingMsg};
+
+use crate::{
+    contract::{CONTRACT_NAME, CONTRACT_VERSION},
+    error::ContractError,
+    msg::{MigrateMsg, Redelegation},
+    program::{main_delegations, record_shares},
+    state::CONFIG,
+};
+
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = cw2::get_contract_version(deps.as_ref().storage)?;
+
+    // can only migrate mars-delegator contract
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::incorrect_contract(CONTRACT_NAME, version.contract));
+    }
+
+    // can only migrate from the version the plan was written for
+    if version.version != msg.from_version {
+        return Err(ContractError::incorrect_version(msg.from_version, version.version));
+    }
+
+    validate_redelegations(&deps, &env, &msg.redelegations)?;
+
+    // update contract version
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // compose redelegate messages
+    let bond_denom = CONFIG.load(deps.storage)?.bond_denom;
+    let msgs = msg
+        .redelegations
+        .iter()
+        .map(|r| StakingMsg::Redelegate {
+            src_validator: r.src.clone(),
+            dst_validator: r.dst.clone(),
+            amount: coin(r.amount.u128(), &bond_denom),
+        })
+        .collect::<Vec<_>>();
+
+    record_shares(&deps.querier, deps.storage, &env.contract.address, None, &msgs)?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "migrate")
+        .add_attribute("contract", CONTRACT_NAME)
+        .add_attribute("old_version", version.version)
+        .add_attribute("new_version", CONTRACT_VERSION))
+}
+
+/// Ensure the redelegations can all be carried out: the destinations are active validators, and
+/// the total moved out of each source doesn't exceed what the contract can currently redelegate
+/// from it. Only the main program's delegations may be moved, as named programs keep track of
+/// theirs per validator.
+fn validate_redelegations(
+    deps: &DepsMut,
+    env: &Env,
+    redelegations: &[Redelegation],
+) -> Result<(), ContractError> {
+    let mut totals: HashMap<&str, u128> = HashMap::new();
+
+    for r in redelegations {
+        if r.amount.is_zero() {
+            return Err(ContractError::invalid_redelegation(
+                &r.src,
+                &r.dst,
+                "amount must be greater than zero",
+            ));
+        }
+
+        if r.src == r.dst {
+            return Err(ContractError::invalid_redelegation(
+                &r.src,
+                &r.dst,
+                "source and destination must differ",
+            ));
+        }
+
+        if deps.querier.query_validator(&r.dst)?.is_none() {
+            return Err(ContractError::validator_not_found(&r.dst));
+        }
+
+        *totals.entry(&r.src).or_default() += r.amount.u128();
+    }
+
+    let main: HashMap<String, u128> =
+        main_delegations(&deps.querier, deps.storage, &env.contract.address)?
+            .into_iter()
+            .map(|(validator, amount)| (validator, amount.u128()))
+            .collect();
+
+    for (src, requested) in totals {
+        let available = deps
+            .querier
+            .query_delegation(&env.contract.address, src)?
+            .map(|d| d.can_redelegate.amount.u128())
+            .unwrap_or(0)
+            .min(main.get(src).copied().unwrap_or(0));
+
+        if requested > available {
+            return Err(ContractError::insufficient_redelegatable(src, requested, available));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{
+        testing::{
+            mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
+        },
+        Addr, Decimal, FullDelegation, OwnedDeps, SubMsg, Uint128, Validator,
+    };
+
+    use super::*;
+    use crate::{
+        msg::{Config, RewardPolicy, DEFAULT_UNBONDING_PERIOD},
+        state::PROGRAM_SHARES,
+    };
+
+    const FROM_VERSION: &str = "1.0.0";
+
+    const BOND_DENOM: &str = "umars";
+
+    fn setup_test() -> OwnedDeps<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies();
+
+        let validators = ["larry", "jake", "pumpkin"].map(|address| Validator {
+            address: address.into(),
+            commission: Decimal::zero(),
+            max_commission: Decimal::zero(),
+            max_change_rate: Decimal::zero(),
+        });
+        // jake's tokens were partially redelegated to it recently
+        let delegations = [("larry", 5000, 5000), ("jake", 5000, 1000)].map(
+            |(validator, amount, can_redelegate)| FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: validator.into(),
+                amount: coin(amount, BOND_DENOM),
+                can_redelegate: coin(can_redelegate, BOND_DENOM),
+                accumulated_rewards: vec![],
+            },
+        );
+        deps.querier.update_staking(BOND_DENOM, &validators, &delegations);
+
+        cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, FROM_VERSION).unwrap();
+        CONFIG
+            .save(
+                deps.as_mut().storage,
+                &Config {
+                    bond_denom: BOND_DENOM.into(),
+                    ending_time: 10000,
+                    validators: vec![],
+                    min_validators: 0,
+                    reward_policy: RewardPolicy::Restake,
+                    reward_withdraw_address: None,
+                    unbonding_period: DEFAULT_UNBONDING_PERIOD,
+                },
+            )
+            .unwrap();
+
+        deps
+    }
+
+    fn redelegation(src: &str, dst: &str, amount: u128) -> Redelegation {
+        Redelegation {
+            src: src.into(),
+            dst: dst.into(),
+            amount: Uint128::new(amount),
+        }
+    }
+
+    fn migrate_msg(redelegations: Vec<Redelegation>) -> MigrateMsg {
+        MigrateMsg {
+            from_version: FROM_VERSION.into(),
+            redelegations,
+        }
+    }
+
+    #[test]
+    fn rejecting_incorrect_contract() {
+        let mut deps = setup_test();
+
+        cw2::set_contract_version(deps.as_mut().storage, "mars-vesting", FROM_VERSION).unwrap();
+
+        let err = migrate(deps.as_mut(), mock_env(), migrate_msg(vec![])).unwrap_err();
+        assert_eq!(err, ContractError::incorrect_contract(CONTRACT_NAME, "mars-vesting"));
+    }
+
+    #[test]
+    fn rejecting_incorrect_version() {
+        let mut deps = setup_test();
+
+        cw2::set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "v1.1.0").unwrap();
+
+        let err = migrate(deps.as_mut(), mock_env(), migrate_msg(vec![])).unwrap_err();
+        assert_eq!(err, ContractError::incorrect_version(FROM_VERSION, "v1.1.0"));
+    }
+
+    #[test]
+    fn rejecting_invalid_redelegations() {
+        let mut deps = setup_test();
+
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![redelegation("larry", "pumpkin", 0)]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::invalid_redelegation(
+                "larry",
+                "pumpkin",
+                "amount must be greater than zero"
+            )
+        );
+
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![redelegation("larry", "larry", 100)]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::invalid_redelegation(
+                "larry",
+                "larry",
+                "source and destination must differ"
+            )
+        );
+
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![redelegation("larry", "jimmy", 100)]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::validator_not_found("jimmy"));
+
+        // the amounts moved out of the same validator add up
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![
+                redelegation("jake", "pumpkin", 600),
+                redelegation("jake", "larry", 600),
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::insufficient_redelegatable("jake", 1200, 1000));
+
+        // the contract doesn't delegate to pumpkin at all
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![redelegation("pumpkin", "larry", 1)]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::insufficient_redelegatable("pumpkin", 1, 0));
+
+        // part of larry's delegation belongs to a named program
+        PROGRAM_SHARES
+            .save(deps.as_mut().storage, ("builders", "larry"), &Uint128::new(3000))
+            .unwrap();
+
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![redelegation("larry", "pumpkin", 2001)]),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::insufficient_redelegatable("larry", 2001, 2000));
+    }
+
+    #[test]
+    fn proper_migration() {
+        let mut deps = setup_test();
+
+        let res = migrate(
+            deps.as_mut(),
+            mock_env(),
+            migrate_msg(vec![
+                redelegation("larry", "pumpkin", 5000),
+                redelegation("jake", "pumpkin", 1000),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(StakingMsg::Redelegate {
+                    src_validator: "larry".into(),
+                    dst_validator: "pumpkin".into(),
+                    amount: coin(5000, BOND_DENOM),
+                }),
+                SubMsg::new(StakingMsg::Redelegate {
+                    src_validator: "jake".into(),
+                    dst_validator: "pumpkin".into(),
+                    amount: coin(1000, BOND_DENOM),
+                }),
+            ],
+        );
+
+        let version = cw2::get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(version.version, CONTRACT_VERSION);
+    }
+}
@@ -1,4 +1,13 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Coin, Uint128};
+pub use mars_pagination::{OrderBy, Paginated};
+
+/// Unbonding period of the Mars Hub staking module: 21 days
+pub const DEFAULT_UNBONDING_PERIOD: u64 = 21 * 24 * 60 * 60;
+
+fn default_unbonding_period() -> u64 {
+    DEFAULT_UNBONDING_PERIOD
+}
 
 #[cw_serde]
 pub struct Config {
@@ -12,20 +21,144 @@ pub struct Config {
     /// Additionally, Mars Hub governance can decide to prematurely end the delegation program if
     /// they see fit, ignoring the ending time, by invoking the `force_unbond` sudo message.
     pub ending_time: u64,
+
+    /// Validators to delegate to, and the relative weight of each.
+    ///
+    /// If empty, tokens are delegated evenly to the entire active validator set.
+    #[serde(default)]
+    pub validators: Vec<WeightedValidator>,
+
+    /// Minimum number of active validators required to bond or rebalance.
+    ///
+    /// Validators that are jailed, tombstoned or otherwise not in the active set are skipped. If
+    /// fewer than this many (and at least one) remain, bonding is rejected rather than
+    /// concentrating the delegations.
+    #[serde(default)]
+    pub min_validators: u32,
+
+    /// What to do with staking rewards claimed by `harvest`.
+    #[serde(default)]
+    pub reward_policy: RewardPolicy,
+
+    /// Address staking rewards are paid out to, if not the contract itself.
+    ///
+    /// When set, rewards claimed by `harvest` go directly to this address, and the reward policy
+    /// doesn't apply.
+    pub reward_withdraw_address: Option<String>,
+
+    /// Time it takes for undelegated tokens to be returned to the contract, in seconds.
+    ///
+    /// Must match the staking module's unbonding time, which contracts can't query. Defaults to 21
+    /// days.
+    #[serde(default = "default_unbonding_period")]
+    pub unbonding_period: u64,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub enum RewardPolicy {
+    /// Delegate the rewards to the validators, in the same way as `bond`.
+    ///
+    /// Only rewards in `bond_denom` can be delegated; others remain in the contract until refunded.
+    #[default]
+    Restake,
+
+    /// Deposit the rewards into the community pool.
+    CommunityPool,
+
+    /// Send the rewards to the given address.
+    Send {
+        recipient: String,
+    },
+}
+
+#[cw_serde]
+pub struct Program {
+    /// Amount of `bond_denom` set aside for the program, delegated when it is bonded.
+    pub budget: Uint128,
+
+    /// The ending time for the program, as UNIX timestamp, after which anyone can unbond it.
+    pub ending_time: u64,
+
+    /// Validators to delegate to, and the relative weight of each.
+    ///
+    /// If empty, tokens are delegated evenly to the entire active validator set.
+    #[serde(default)]
+    pub validators: Vec<WeightedValidator>,
+
+    /// Address the budget is returned to when refunded. If `None`, it is donated to the community
+    /// pool.
+    pub refund_address: Option<String>,
+}
+
+#[cw_serde]
+pub struct WeightedValidator {
+    /// Operator address of the validator.
+    pub address: String,
+
+    /// The validator's share of delegations, relative to the sum of all validators' weights.
+    pub weight: u64,
 }
 
 pub type InstantiateMsg = Config;
 
+/// Messages that act on delegations take an optional program id. If omitted, they act on the main
+/// program, i.e. the one described by `Config`, which holds all tokens not set aside for other
+/// programs.
 #[cw_serde]
 pub enum SudoMsg {
     /// Delegate tokens that the contract holds evenly to the current validator set.
-    Bond {},
+    ///
+    /// For a named program, its budget is delegated to its own validators.
+    Bond {
+        program: Option<String>,
+    },
 
     /// Forcibly unbond the delegations.
     ///
     /// This "sudo" message can only be invoked by the gov module, and ignores whether the
     /// `ending_time` has been reached.
-    ForceUnbond {},
+    ForceUnbond {
+        program: Option<String>,
+    },
+
+    /// Update the configuration of the main program. Fields left empty are unchanged.
+    ///
+    /// The ending time must be in the future, and the bond denom can't be changed while any tokens
+    /// are bonded or unbonding.
+    UpdateConfig {
+        bond_denom: Option<String>,
+        ending_time: Option<u64>,
+        min_validators: Option<u32>,
+        reward_policy: Option<RewardPolicy>,
+        unbonding_period: Option<u64>,
+    },
+
+    /// Set aside part of the contract's tokens for a new delegation program, with its own
+    /// validators, ending time and refund destination.
+    CreateProgram {
+        id: String,
+        program: Program,
+    },
+
+    /// Cancel a named program that hasn't been bonded yet, returning its budget to the program's
+    /// refund destination.
+    CancelProgram {
+        id: String,
+    },
+
+    /// Replace the validators to delegate to, and their weights.
+    ///
+    /// Only affects subsequent bondings; existing delegations are not moved.
+    UpdateValidators {
+        validators: Vec<WeightedValidator>,
+    },
+
+    /// Set the address staking rewards are paid out to, e.g. a treasury. If `None`, rewards are paid
+    /// out to the contract itself.
+    SetRewardWithdrawAddress {
+        address: Option<String>,
+    },
 }
 
 #[cw_serde]
@@ -33,10 +166,33 @@ pub enum ExecuteMsg {
     /// Unbond the delegations.
     ///
     /// Can be invoked by anyone after `ending_time` is reached.
-    Unbond {},
+    Unbond {
+        program: Option<String>,
+    },
 
     /// Donate all coins held by the contract to the community pool.
-    Refund {},
+    ///
+    /// For a named program, return its budget to the program's refund address instead, once it has
+    /// been unbonded.
+    ///
+    /// Can be invoked by anyone once all unbonding batches have completed.
+    Refund {
+        program: Option<String>,
+    },
+
+    /// Redelegate from validators holding more than their share of the delegations to those
+    /// holding less, so that the delegations match the configured weights.
+    ///
+    /// Can be invoked by anyone.
+    Rebalance {},
+
+    /// Claim staking rewards from all validators, and handle them according to the reward policy.
+    ///
+    /// This includes rewards earned on the delegations of named programs, which are restaked into
+    /// the main program under the `Restake` policy.
+    ///
+    /// Can be invoked by anyone.
+    Harvest {},
 }
 
 #[cw_serde]
@@ -45,4 +201,192 @@ pub enum QueryMsg {
     /// Return the contract configuration.
     #[returns(Config)]
     Config {},
+
+    /// Return the total amount of staking rewards claimed by `harvest` so far.
+    #[returns(RewardsResponse)]
+    Rewards {},
+
+    /// Return how much of the bond denom is bonded, unbonding, and held by the contract, along
+    /// with the unbonding batches.
+    #[returns(StatusResponse)]
+    Status {},
+
+    /// Return the contract's delegations, along with rewards pending on each.
+    #[returns(Vec<DelegationResponse>)]
+    Delegations {},
+
+    /// Return all coins held by the contract.
+    #[returns(Vec<Coin>)]
+    Balances {},
+
+    /// Return the current phase of the main delegation program.
+    #[returns(Phase)]
+    Phase {},
+
+    /// Return a named delegation program.
+    #[returns(ProgramResponse)]
+    Program {
+        id: String,
+    },
+
+    /// Enumerate named delegation programs, by id.
+    #[returns(Paginated<ProgramResponse>)]
+    Programs {
+        start_after: Option<String>,
+        start_before: Option<String>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+
+    /// Return past bond, unbond and refund actions, by id.
+    #[returns(Paginated<HistoryEntry, u64>)]
+    History {
+        start_after: Option<u64>,
+        start_before: Option<u64>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+}
+
+#[cw_serde]
+pub struct RewardsResponse {
+    /// Rewards claimed across all harvests, one coin per denom.
+    ///
+    /// Rewards the staking module paid out on its own, e.g. when delegations change, are not
+    /// included.
+    pub total_harvested: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct UnbondingBatch {
+    /// Sequential identifier of the batch.
+    pub id: u64,
+
+    /// The named program the batch belongs to, or `None` for the main program.
+    pub program: Option<String>,
+
+    /// Amount of `bond_denom` undelegated.
+    pub amount: Uint128,
+
+    /// Time when the undelegated tokens are expected to be returned, as UNIX timestamp.
+    pub completion_time: u64,
+}
+
+#[cw_serde]
+pub struct StatusResponse {
+    /// Amount currently delegated to validators.
+    pub bonded: Uint128,
+
+    /// Amount undelegated but not yet returned to the contract.
+    pub unbonding: Uint128,
+
+    /// Amount held by the contract.
+    pub liquid: Uint128,
+
+    /// Unbonding batches not yet cleared by a refund, including completed ones.
+    pub batches: Vec<UnbondingBatch>,
+}
+
+#[cw_serde]
+pub struct DelegationResponse {
+    /// Operator address of the validator.
+    pub validator: String,
+
+    /// Amount delegated to the validator.
+    pub amount: Coin,
+
+    /// Staking rewards accrued but not yet claimed.
+    pub pending_rewards: Vec<Coin>,
+}
+
+#[cw_serde]
+pub enum Phase {
+    /// Nothing has been delegated yet.
+    Bonding,
+
+    /// Tokens are delegated, and the ending time is not reached yet.
+    Active,
+
+    /// The ending time is reached, or the delegations have been unbonded and the tokens are
+    /// waiting to be refunded.
+    Ended,
+
+    /// Undelegated tokens are yet to be returned to the contract.
+    Unbonding,
+
+    /// Everything has been unbonded and refunded.
+    Refunded,
+}
+
+#[cw_serde]
+pub enum Action {
+    Bond,
+    Unbond,
+    ForceUnbond,
+    Refund,
+}
+
+#[cw_serde]
+pub struct HistoryEntry {
+    /// Sequential identifier of the entry.
+    pub id: u64,
+
+    /// The named program acted on, or `None` for the main program.
+    pub program: Option<String>,
+
+    /// What was done.
+    pub action: Action,
+
+    /// Coins delegated, undelegated or refunded.
+    pub amount: Vec<Coin>,
+
+    /// Time of the action, as UNIX timestamp.
+    pub timestamp: u64,
+}
+
+#[cw_serde]
+pub struct ProgramResponse {
+    /// Identifier of the program.
+    pub id: String,
+
+    /// Parameters of the program.
+    pub program: Program,
+
+    /// Current phase of the program.
+    pub phase: Phase,
+
+    /// Amount delegated to each validator on behalf of the program.
+    pub delegations: Vec<ProgramDelegation>,
+}
+
+#[cw_serde]
+pub struct ProgramDelegation {
+    /// Operator address of the validator.
+    pub validator: String,
+
+    /// Amount of `bond_denom` delegated.
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {
+    /// The version the contract is being migrated from. The migration is rejected if the contract
+    /// is at any other version.
+    pub from_version: String,
+
+    /// Redelegations to carry out as part of the migration, e.g. a reshuffle decided by governance.
+    #[serde(default)]
+    pub redelegations: Vec<Redelegation>,
+}
+
+#[cw_serde]
+pub struct Redelegation {
+    /// Operator address of the validator to redelegate from.
+    pub src: String,
+
+    /// Operator address of the validator to redelegate to.
+    pub dst: String,
+
+    /// Amount of `bond_denom` to redelegate.
+    pub amount: Uint128,
 }
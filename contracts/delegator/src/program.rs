@@ -0,0 +1,573 @@
+use std::collections::BTreeMap;
+
+use cosmwasm_std::{
+    coin, coins, Addr, BankMsg, Deps, DepsMut, Env, Order, QuerierWrapper, Response, StakingMsg,
+    StdResult, Storage, Uint128,
+};
+use mars_pagination as pagination;
+
+use crate::{
+    error::ContractError,
+    execute::{
+        compute_allocation, record_history, record_unbonding, validate_ending_time,
+        validate_validators,
+    },
+    msg::{Action, OrderBy, Paginated, Phase, Program, ProgramDelegation, ProgramResponse},
+    state::{
+        CONFIG, DELEGATION_SHARES, PROGRAMS, PROGRAM_PHASES, PROGRAM_SHARES, UNBONDING_BATCHES,
+    },
+    types::MarsMsg,
+};
+
+//--------------------------------------------------------------------------------------------------
+// Executions
+//--------------------------------------------------------------------------------------------------
+
+pub fn create_program(
+    deps: DepsMut,
+    env: Env,
+    id: String,
+    program: Program,
+) -> Result<Response<MarsMsg>, ContractError> {
+    if PROGRAMS.has(deps.storage, &id) {
+        return Err(ContractError::program_exists(id));
+    }
+
+    validate_ending_time(&env, program.ending_time)?;
+
+    if program.budget.is_zero() {
+        return Err(ContractError::NothingToBond);
+    }
+
+    validate_validators(&deps.querier, &program.validators)?;
+
+    if let Some(refund_address) = &program.refund_address {
+        deps.api.addr_validate(refund_address)?;
+    }
+
+    PROGRAMS.save(deps.storage, &id, &program)?;
+    PROGRAM_PHASES.save(deps.storage, &id, &Phase::Bonding)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "periphery/delegator/create_program")
+        .add_attribute("id", id)
+        .add_attribute("budget", program.budget)
+        .add_attribute("ending_time", program.ending_time.to_string()))
+}
+
+pub fn bond(deps: DepsMut, env: Env, id: String) -> Result<Response<MarsMsg>, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let program = PROGRAMS.load(deps.storage, &id)?;
+    assert_phase(deps.storage, &id, Phase::Bonding)?;
+
+    // the budget must be covered by tokens not set aside for other programs
+    let balance = deps.querier.query_balance(&env.contract.address, &cfg.bond_denom)?.amount;
+    let reserved = reserved_liquid(deps.storage, env.block.time.seconds(), Some(&id))?;
+    let available = balance.saturating_sub(reserved);
+    if available < program.budget {
+        return Err(ContractError::insufficient_funds(id, program.budget.u128(), available.u128()));
+    }
+
+    let allocation = compute_allocation(
+        &deps.querier,
+        &program.validators,
+        cfg.min_validators,
+        program.budget.u128(),
+    )?;
+
+    let msgs = allocation
+        .into_iter()
+        // delegating zero tokens would fail
+        .filter(|(_, tokens)| *tokens > 0)
+        .map(|(validator, tokens)| StakingMsg::Delegate {
+            validator,
+            amount: coin(tokens, &cfg.bond_denom),
+        })
+        .collect::<Vec<_>>();
+
+    record_shares(&deps.querier, deps.storage, &env.contract.address, Some(&id), &msgs)?;
+
+    PROGRAM_PHASES.save(deps.storage, &id, &Phase::Active)?;
+
+    record_history(
+        deps.storage,
+        &env,
+        Some(&id),
+        Action::Bond,
+        coins(program.budget.u128(), &cfg.bond_denom),
+    )?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "periphery/delegator/bond")
+        .add_attribute("program", id)
+        .add_attribute("amount", format!("{}{}", program.budget, cfg.bond_denom)))
+}
+
+pub fn unbond(
+    deps: DepsMut,
+    env: Env,
+    id: String,
+    forced: bool,
+) -> Result<Response<MarsMsg>, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let program = PROGRAMS.load(deps.storage, &id)?;
+    assert_phase(deps.storage, &id, Phase::Active)?;
+
+    let current_time = env.block.time.seconds();
+    if !forced && current_time < program.ending_time {
+        return Err(ContractError::ending_time_not_reached(program.ending_time, current_time));
+    }
+
+    // the tokens backing the program's shares, which slashing may have reduced
+    let msgs = program_delegations(&deps.querier, deps.storage, &env.contract.address, &id)?
+        .into_iter()
+        .map(|(validator, amount)| StakingMsg::Undelegate {
+            validator,
+            amount: coin(amount.u128(), &cfg.bond_denom),
+        })
+        .collect::<Vec<_>>();
+
+    // all of the program's shares are redeemed, even those no longer worth a token; undelegating
+    // zero tokens would fail though
+    record_shares(&deps.querier, deps.storage, &env.contract.address, Some(&id), &msgs)?;
+    let msgs = msgs
+        .into_iter()
+        .filter(
+            |msg| !matches!(msg, StakingMsg::Undelegate { amount, .. } if amount.amount.is_zero()),
+        )
+        .collect::<Vec<_>>();
+
+    PROGRAM_PHASES.save(deps.storage, &id, &Phase::Unbonding)?;
+
+    let action = if forced {
+        Action::ForceUnbond
+    } else {
+        Action::Unbond
+    };
+    let completion_time = record_unbonding(deps, &env, &cfg, Some(&id), &msgs, action)?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute(
+            "action",
+            if forced {
+                "periphery/delegator/force_unbond"
+            } else {
+                "periphery/delegator/unbond"
+            },
+        )
+        .add_attribute("program", id)
+        .add_attribute("completion_time", completion_time.to_string()))
+}
+
+pub fn refund(deps: DepsMut, env: Env, id: String) -> Result<Response<MarsMsg>, ContractError> {
+    assert_phase(deps.storage, &id, Phase::Unbonding)?;
+
+    let current_time = env.block.time.seconds();
+    let batches = program_batches(deps.storage, &id)?;
+
+    if let Some(completion_time) = batches.iter().map(|(_, c, _)| *c).max() {
+        if current_time < completion_time {
+            return Err(ContractError::unbonding_not_complete(completion_time, current_time));
+        }
+    }
+
+    for (batch_id, ..) in &batches {
+        UNBONDING_BATCHES.remove(deps.storage, *batch_id);
+    }
+
+    let amount = batches.iter().map(|(.., amount)| *amount).sum();
+
+    payout(deps, env, id, amount)
+}
+
+/// Cancel a program that hasn't been bonded yet, returning its budget to the refund destination.
+pub fn cancel_program(
+    deps: DepsMut,
+    env: Env,
+    id: String,
+) -> Result<Response<MarsMsg>, ContractError> {
+    let program = PROGRAMS.load(deps.storage, &id)?;
+    assert_phase(deps.storage, &id, Phase::Bonding)?;
+
+    payout(deps, env, id, program.budget)
+}
+
+/// Send a program's tokens to its refund destination, and mark it as refunded.
+fn payout(
+    deps: DepsMut,
+    env: Env,
+    id: String,
+    amount: Uint128,
+) -> Result<Response<MarsMsg>, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let program = PROGRAMS.load(deps.storage, &id)?;
+
+    // slashing may have returned less than was delegated; never dip into tokens belonging to the
+    // main program or other programs
+    let balance = deps.querier.query_balance(&env.contract.address, &cfg.bond_denom)?.amount;
+    let reserved = reserved_liquid(deps.storage, env.block.time.seconds(), Some(&id))?;
+    let amount = amount.min(balance.saturating_sub(reserved));
+
+    if amount.is_zero() {
+        return Err(ContractError::NothingToRefund);
+    }
+
+    PROGRAM_PHASES.save(deps.storage, &id, &Phase::Refunded)?;
+
+    record_history(
+        deps.storage,
+        &env,
+        Some(&id),
+        Action::Refund,
+        coins(amount.u128(), &cfg.bond_denom),
+    )?;
+
+    let amount = coins(amount.u128(), &cfg.bond_denom);
+    let res = match program.refund_address {
+        Some(to_address) => Response::new().add_message(BankMsg::Send {
+            to_address,
+            amount,
+        }),
+        None => Response::new().add_message(MarsMsg::FundCommunityPool {
+            amount,
+        }),
+    };
+
+    Ok(res.add_attribute("action", "periphery/delegator/refund").add_attribute("program", id))
+}
+
+fn assert_phase(storage: &dyn Storage, id: &str, expected: Phase) -> Result<(), ContractError> {
+    let phase = PROGRAM_PHASES.load(storage, id)?;
+
+    if phase != expected {
+        return Err(ContractError::invalid_program_phase(id, &phase));
+    }
+
+    Ok(())
+}
+
+//--------------------------------------------------------------------------------------------------
+// Accounting
+//--------------------------------------------------------------------------------------------------
+
+/// Amount of `bond_denom` held by the contract that belongs to named programs, optionally excluding
+/// one of them: the budgets of programs not bonded yet, and the completed unbondings of programs
+/// not refunded yet.
+pub fn reserved_liquid(
+    storage: &dyn Storage,
+    current_time: u64,
+    exclude: Option<&str>,
+) -> StdResult<Uint128> {
+    let mut reserved = Uint128::zero();
+
+    for res in PROGRAMS.range(storage, None, None, Order::Ascending) {
+        let (id, program) = res?;
+        if Some(id.as_str()) == exclude {
+            continue;
+        }
+
+        match PROGRAM_PHASES.load(storage, &id)? {
+            Phase::Bonding => reserved += program.budget,
+            Phase::Unbonding => {
+                reserved += program_batches(storage, &id)?
+                    .into_iter()
+                    .filter(|(_, completion_time, _)| *completion_time <= current_time)
+                    .map(|(.., amount)| amount)
+                    .sum::<Uint128>();
+            }
+            _ => (),
+        }
+    }
+
+    Ok(reserved)
+}
+
+/// Tokens the contract delegates to each validator, and the number of shares each delegation is
+/// divided into.
+fn delegation_shares(
+    querier: &QuerierWrapper,
+    storage: &dyn Storage,
+    delegator_addr: &Addr,
+) -> StdResult<Vec<(String, (Uint128, Uint128))>> {
+    querier
+        .query_all_delegations(delegator_addr)?
+        .into_iter()
+        .map(|delegation| {
+            let tokens = delegation.amount.amount;
+            let shares =
+                DELEGATION_SHARES.may_load(storage, &delegation.validator)?.unwrap_or(tokens);
+            Ok((delegation.validator, (tokens, shares)))
+        })
+        .collect()
+}
+
+/// Tokens backing the given shares of a delegation, rounded down
+fn shares_to_tokens(shares: Uint128, (tokens, total_shares): (Uint128, Uint128)) -> Uint128 {
+    if total_shares.is_zero() {
+        return Uint128::zero();
+    }
+
+    shares.multiply_ratio(tokens, total_shares)
+}
+
+/// Shares of the delegation to a validator held by named programs
+fn program_shares_of(storage: &dyn Storage, validator: &str) -> StdResult<Vec<Uint128>> {
+    PROGRAM_SHARES
+        .range(storage, None, None, Order::Ascending)
+        .filter_map(|res| match res {
+            Ok(((_, v), shares)) if v == validator => Some(Ok(shares)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .collect()
+}
+
+/// Update the shares of the contract's delegations for the given staking messages, sent on behalf
+/// of a named program, or of the main program if `None`. Must be called before the messages are
+/// executed, as the exchange rate between tokens and shares is taken from the current delegations.
+///
+/// Delegating issues shares at the current rate. Undelegating redeems all of a named program's
+/// shares, as named programs only ever undelegate in full, or for the main program, the shares
+/// backing the tokens undelegated, rounded up.
+pub(crate) fn record_shares(
+    querier: &QuerierWrapper,
+    storage: &mut dyn Storage,
+    delegator_addr: &Addr,
+    program: Option<&str>,
+    msgs: &[StakingMsg],
+) -> StdResult<()> {
+    let mut delegations: BTreeMap<_, _> =
+        delegation_shares(querier, storage, delegator_addr)?.into_iter().collect();
+
+    for msg in msgs {
+        match msg {
+            StakingMsg::Delegate {
+                validator,
+                amount,
+            } => issue_shares(storage, &mut delegations, program, validator, amount.amount)?,
+            StakingMsg::Undelegate {
+                validator,
+                amount,
+            } => redeem_shares(storage, &mut delegations, program, validator, amount.amount)?,
+            StakingMsg::Redelegate {
+                src_validator,
+                dst_validator,
+                amount,
+            } => {
+                redeem_shares(storage, &mut delegations, program, src_validator, amount.amount)?;
+                issue_shares(storage, &mut delegations, program, dst_validator, amount.amount)?;
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn issue_shares(
+    storage: &mut dyn Storage,
+    delegations: &mut BTreeMap<String, (Uint128, Uint128)>,
+    program: Option<&str>,
+    validator: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    let (tokens, shares) = delegations.entry(validator.to_string()).or_default();
+
+    let issued = if tokens.is_zero() || shares.is_zero() {
+        amount
+    } else {
+        amount.multiply_ratio(*shares, *tokens)
+    };
+
+    *tokens += amount;
+    *shares += issued;
+    DELEGATION_SHARES.save(storage, validator, shares)?;
+
+    if let Some(id) = program {
+        PROGRAM_SHARES.update(storage, (id, validator), |held| -> StdResult<_> {
+            Ok(held.unwrap_or_default() + issued)
+        })?;
+    }
+
+    Ok(())
+}
+
+fn redeem_shares(
+    storage: &mut dyn Storage,
+    delegations: &mut BTreeMap<String, (Uint128, Uint128)>,
+    program: Option<&str>,
+    validator: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    let (tokens, shares) = delegations.entry(validator.to_string()).or_default();
+
+    let redeemed = match program {
+        Some(id) => {
+            let held = PROGRAM_SHARES.may_load(storage, (id, validator))?.unwrap_or_default();
+            PROGRAM_SHARES.remove(storage, (id, validator));
+            held
+        }
+        None => {
+            let program_shares = program_shares_of(storage, validator)?.into_iter().sum();
+            let held = shares.saturating_sub(program_shares);
+            if tokens.is_zero() {
+                held
+            } else {
+                let mut redeemed = amount.multiply_ratio(*shares, *tokens);
+                if redeemed.full_mul(*tokens) < amount.full_mul(*shares) {
+                    redeemed += Uint128::one();
+                }
+                redeemed.min(held)
+            }
+        }
+    };
+
+    *tokens = tokens.saturating_sub(amount);
+    *shares -= redeemed;
+    if shares.is_zero() {
+        DELEGATION_SHARES.remove(storage, validator);
+    } else {
+        DELEGATION_SHARES.save(storage, validator, shares)?;
+    }
+
+    Ok(())
+}
+
+/// Tokens backing a named program's shares of the contract's delegations
+pub fn program_delegations(
+    querier: &QuerierWrapper,
+    storage: &dyn Storage,
+    delegator_addr: &Addr,
+    id: &str,
+) -> StdResult<Vec<(String, Uint128)>> {
+    let delegations: BTreeMap<_, _> =
+        delegation_shares(querier, storage, delegator_addr)?.into_iter().collect();
+
+    PROGRAM_SHARES
+        .prefix(id)
+        .range(storage, None, None, Order::Ascending)
+        .map(|res| {
+            let (validator, shares) = res?;
+            let tokens = delegations
+                .get(&validator)
+                .map(|delegation| shares_to_tokens(shares, *delegation))
+                .unwrap_or_default();
+            Ok((validator, tokens))
+        })
+        .collect()
+}
+
+/// Delegations that belong to the main program, i.e. the contract's delegations minus the tokens
+/// backing named programs' shares.
+///
+/// The staking module records a single delegation per validator, so the programs' shares are
+/// tracked by the contract itself. Named programs' tokens are rounded down, so the main program is
+/// left with any rounding dust.
+pub fn main_delegations(
+    querier: &QuerierWrapper,
+    storage: &dyn Storage,
+    delegator_addr: &Addr,
+) -> StdResult<Vec<(String, Uint128)>> {
+    let mut assigned: BTreeMap<String, Vec<Uint128>> = BTreeMap::new();
+    for res in PROGRAM_SHARES.range(storage, None, None, Order::Ascending) {
+        let ((_, validator), shares) = res?;
+        assigned.entry(validator).or_default().push(shares);
+    }
+
+    Ok(delegation_shares(querier, storage, delegator_addr)?
+        .into_iter()
+        .filter_map(|(validator, delegation)| {
+            let assigned = assigned
+                .get(&validator)
+                .into_iter()
+                .flatten()
+                .map(|shares| shares_to_tokens(*shares, delegation))
+                .sum::<Uint128>();
+            let amount = delegation.0.saturating_sub(assigned);
+            (!amount.is_zero()).then_some((validator, amount))
+        })
+        .collect())
+}
+
+/// Unbonding batches of a named program, as (batch id, completion time, amount)
+fn program_batches(storage: &dyn Storage, id: &str) -> StdResult<Vec<(u64, u64, Uint128)>> {
+    UNBONDING_BATCHES
+        .range(storage, None, None, Order::Ascending)
+        .filter_map(|res| match res {
+            Ok((_, batch)) if batch.program.as_deref() == Some(id) => {
+                Some(Ok((batch.id, batch.completion_time, batch.amount)))
+            }
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .collect()
+}
+
+//--------------------------------------------------------------------------------------------------
+// Queries
+//--------------------------------------------------------------------------------------------------
+
+pub fn query_program(deps: Deps, env: Env, id: String) -> StdResult<ProgramResponse> {
+    let program = PROGRAMS.load(deps.storage, &id)?;
+    compute_program_response(deps, &env, id, program)
+}
+
+pub fn query_programs(
+    deps: Deps,
+    env: Env,
+    start_after: Option<String>,
+    start_before: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Paginated<ProgramResponse>> {
+    let (min, max) = pagination::bounds(start_after.as_deref(), start_before.as_deref());
+
+    let total = PROGRAMS.keys_raw(deps.storage, None, None, Order::Ascending).count();
+
+    let programs =
+        PROGRAMS.range(deps.storage, min, max, order.unwrap_or_default().into()).map(|res| {
+            let (id, program) = res?;
+            compute_program_response(deps, &env, id, program)
+        });
+
+    pagination::paginate(programs, pagination::limit(limit), total as u64, |res| res.id.clone())
+}
+
+fn compute_program_response(
+    deps: Deps,
+    env: &Env,
+    id: String,
+    program: Program,
+) -> StdResult<ProgramResponse> {
+    let storage = deps.storage;
+    let current_time = env.block.time.seconds();
+
+    let phase = match PROGRAM_PHASES.load(storage, &id)? {
+        Phase::Active if current_time >= program.ending_time => Phase::Ended,
+        Phase::Unbonding
+            if program_batches(storage, &id)?
+                .iter()
+                .all(|(_, completion_time, _)| *completion_time <= current_time) =>
+        {
+            Phase::Ended
+        }
+        phase => phase,
+    };
+
+    let delegations = program_delegations(&deps.querier, storage, &env.contract.address, &id)?
+        .into_iter()
+        .map(|(validator, amount)| ProgramDelegation {
+            validator,
+            amount,
+        })
+        .collect();
+
+    Ok(ProgramResponse {
+        id,
+        program,
+        phase,
+        delegations,
+    })
+}
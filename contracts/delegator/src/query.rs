@@ -1,7 +1,147 @@
-use cosmwasm_std::{Deps, StdResult};
+use cosmwasm_std::{Coin, Deps, Env, Order, StdResult, Uint128};
+use mars_pagination as pagination;
 
-use crate::{msg::Config, state::CONFIG};
+use crate::{
+    msg::{
+        Action, Config, DelegationResponse, HistoryEntry, OrderBy, Paginated, Phase,
+        RewardsResponse, StatusResponse,
+    },
+    program::main_delegations,
+    state::{CONFIG, HARVESTED, HISTORY, UNBONDING_BATCHES},
+};
 
 pub fn query_config(deps: Deps) -> StdResult<Config> {
     CONFIG.load(deps.storage)
 }
+
+pub fn query_rewards(deps: Deps) -> StdResult<RewardsResponse> {
+    let total_harvested = HARVESTED
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|res| {
+            let (denom, amount) = res?;
+            Ok(Coin {
+                denom,
+                amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(RewardsResponse {
+        total_harvested,
+    })
+}
+
+pub fn query_status(deps: Deps, env: Env) -> StdResult<StatusResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let current_time = env.block.time.seconds();
+
+    let bonded = deps
+        .querier
+        .query_all_delegations(&env.contract.address)?
+        .into_iter()
+        .filter(|delegation| delegation.amount.denom == cfg.bond_denom)
+        .map(|delegation| delegation.amount.amount)
+        .sum();
+
+    let batches = UNBONDING_BATCHES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|res| res.map(|(_, batch)| batch))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // once a batch completes, its tokens are part of the contract's balance
+    let unbonding = batches
+        .iter()
+        .filter(|batch| batch.completion_time > current_time)
+        .map(|batch| batch.amount)
+        .sum::<Uint128>();
+
+    let liquid = deps.querier.query_balance(&env.contract.address, &cfg.bond_denom)?.amount;
+
+    Ok(StatusResponse {
+        bonded,
+        unbonding,
+        liquid,
+        batches,
+    })
+}
+
+pub fn query_delegations(deps: Deps, env: Env) -> StdResult<Vec<DelegationResponse>> {
+    deps.querier
+        .query_all_delegations(&env.contract.address)?
+        .into_iter()
+        .map(|delegation| {
+            // only the full delegation includes the accrued rewards
+            let pending_rewards = deps
+                .querier
+                .query_delegation(&env.contract.address, &delegation.validator)?
+                .map(|full| full.accumulated_rewards)
+                .unwrap_or_default();
+
+            Ok(DelegationResponse {
+                validator: delegation.validator,
+                amount: delegation.amount,
+                pending_rewards,
+            })
+        })
+        .collect()
+}
+
+pub fn query_balances(deps: Deps, env: Env) -> StdResult<Vec<Coin>> {
+    deps.querier.query_all_balances(env.contract.address)
+}
+
+pub fn query_phase(deps: Deps, env: Env) -> StdResult<Phase> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let current_time = env.block.time.seconds();
+
+    // only the main program's batches, delegations and actions are considered
+    let batches = UNBONDING_BATCHES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|res| res.map(|(_, batch)| batch))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    if batches.iter().any(|batch| batch.program.is_none() && batch.completion_time > current_time) {
+        return Ok(Phase::Unbonding);
+    }
+
+    if !main_delegations(&deps.querier, deps.storage, &env.contract.address)?.is_empty() {
+        return Ok(if current_time < cfg.ending_time {
+            Phase::Active
+        } else {
+            Phase::Ended
+        });
+    }
+
+    // nothing is delegated; tell apart a program that hasn't started from one that has finished
+    // by its last recorded action
+    let last_action = HISTORY
+        .range(deps.storage, None, None, Order::Descending)
+        .map(|res| res.map(|(_, entry)| entry))
+        .find(|res| !matches!(res, Ok(entry) if entry.program.is_some()))
+        .transpose()?
+        .map(|entry| entry.action);
+
+    Ok(match last_action {
+        None | Some(Action::Bond) => Phase::Bonding,
+        Some(Action::Unbond | Action::ForceUnbond) => Phase::Ended,
+        Some(Action::Refund) => Phase::Refunded,
+    })
+}
+
+pub fn query_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    start_before: Option<u64>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<Paginated<HistoryEntry, u64>> {
+    let (min, max) = pagination::bounds(start_after, start_before);
+
+    let total = HISTORY.keys_raw(deps.storage, None, None, Order::Ascending).count();
+
+    let entries = HISTORY
+        .range(deps.storage, min, max, order.unwrap_or_default().into())
+        .map(|res| res.map(|(_, entry)| entry));
+
+    pagination::paginate(entries, pagination::limit(limit), total as u64, |entry| entry.id)
+}
@@ -1,5 +1,38 @@
-use cw_storage_plus::Item;
+use cosmwasm_std::Uint128;
+use cw_storage_plus::{Item, Map};
 
-use crate::msg::Config;
+use crate::msg::{Config, HistoryEntry, Phase, Program, UnbondingBatch};
 
 pub const CONFIG: Item<Config> = Item::new("config");
+
+/// Total staking rewards claimed by `harvest`, indexed by denom
+pub const HARVESTED: Map<&str, Uint128> = Map::new("harvested");
+
+/// Undelegations yet to be cleared by a refund, indexed by id
+pub const UNBONDING_BATCHES: Map<u64, UnbondingBatch> = Map::new("unbonding_batches");
+
+/// Number of unbonding batches ever created, used to assign ids
+pub const UNBONDING_BATCH_COUNT: Item<u64> = Item::new("unbonding_batch_count");
+
+/// Past bond, unbond and refund actions, indexed by id
+pub const HISTORY: Map<u64, HistoryEntry> = Map::new("history");
+
+/// Number of history entries ever recorded, used to assign ids
+pub const HISTORY_COUNT: Item<u64> = Item::new("history_count");
+
+/// Named delegation programs, indexed by id
+pub const PROGRAMS: Map<&str, Program> = Map::new("programs");
+
+/// Stage each named program is in, indexed by program id. Only `Bonding`, `Active`, `Unbonding`
+/// and `Refunded` are stored; `Ended` is derived from the time.
+pub const PROGRAM_PHASES: Map<&str, Phase> = Map::new("program_phases");
+
+/// Number of shares the contract's delegation to each validator is divided into, among the main
+/// program and named programs. Slashing reduces the tokens backing the shares, so each program
+/// bears it in proportion to its shares. Delegations without an entry have one share per token,
+/// all held by the main program.
+pub const DELEGATION_SHARES: Map<&str, Uint128> = Map::new("delegation_shares");
+
+/// Shares of the contract's delegations held by named programs, indexed by program id and
+/// validator; the main program holds the rest
+pub const PROGRAM_SHARES: Map<(&str, &str), Uint128> = Map::new("program_shares");
@@ -1,15 +1,21 @@
 use cosmwasm_std::{
-    coin, coins, from_json,
+    attr, coin, coins, from_json,
     testing::{
         mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
         MOCK_CONTRACT_ADDR,
     },
-    Addr, Decimal, Empty, Env, FullDelegation, OwnedDeps, StakingMsg, SubMsg, Timestamp, Validator,
+    Addr, BankMsg, Coin, Decimal, DistributionMsg, Empty, Env, FullDelegation, OwnedDeps,
+    StakingMsg, SubMsg, Timestamp, Uint128, Validator,
 };
 use mars_delegator::{
     contract::{execute, instantiate, query, sudo},
     error::ContractError,
-    msg::{Config, ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg},
+    msg::{
+        Action, Config, DelegationResponse, ExecuteMsg, HistoryEntry, InstantiateMsg, OrderBy,
+        Paginated, Phase, Program, ProgramDelegation, ProgramResponse, QueryMsg, RewardPolicy,
+        RewardsResponse, StatusResponse, SudoMsg, UnbondingBatch, WeightedValidator,
+        DEFAULT_UNBONDING_PERIOD,
+    },
     types::MarsMsg,
 };
 
@@ -21,6 +27,19 @@ fn mock_env_at_timestamp(timestamp: u64) -> Env {
     env
 }
 
+fn full_delegations(delegations: &[(&str, u128)]) -> Vec<FullDelegation> {
+    delegations
+        .iter()
+        .map(|(validator, amount)| FullDelegation {
+            delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+            validator: validator.to_string(),
+            amount: coin(*amount, BOND_DENOM),
+            can_redelegate: coin(*amount, BOND_DENOM),
+            accumulated_rewards: vec![],
+        })
+        .collect()
+}
+
 fn setup_test() -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
     let mut deps = mock_dependencies();
 
@@ -75,11 +94,16 @@ fn setup_test() -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
     // instantiate the contract
     instantiate(
         deps.as_mut(),
-        mock_env(),
+        mock_env_at_timestamp(0),
         mock_info("deployer", &coins(10000, BOND_DENOM)),
         InstantiateMsg {
             bond_denom: BOND_DENOM.into(),
             ending_time: 10000,
+            validators: vec![],
+            min_validators: 0,
+            reward_policy: RewardPolicy::Restake,
+            reward_withdraw_address: None,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
         },
     )
     .unwrap();
@@ -93,11 +117,16 @@ fn instantiating() {
 
     let res = instantiate(
         deps.as_mut(),
-        mock_env(),
+        mock_env_at_timestamp(0),
         mock_info("deployer", &coins(10000, BOND_DENOM)),
         InstantiateMsg {
             bond_denom: BOND_DENOM.into(),
             ending_time: 10000,
+            validators: vec![],
+            min_validators: 0,
+            reward_policy: RewardPolicy::Restake,
+            reward_withdraw_address: None,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
         },
     )
     .unwrap();
@@ -111,10 +140,140 @@ fn instantiating() {
         Config {
             bond_denom: "umars".into(),
             ending_time: 10000,
+            validators: vec![],
+            min_validators: 0,
+            reward_policy: RewardPolicy::Restake,
+            reward_withdraw_address: None,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
         },
     );
 }
 
+#[test]
+fn rejecting_invalid_config() {
+    let mut deps = mock_dependencies();
+
+    let msg = InstantiateMsg {
+        bond_denom: BOND_DENOM.into(),
+        ending_time: 10000,
+        validators: vec![],
+        min_validators: 0,
+        reward_policy: RewardPolicy::Restake,
+        reward_withdraw_address: None,
+        unbonding_period: DEFAULT_UNBONDING_PERIOD,
+    };
+
+    let err = instantiate(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        mock_info("deployer", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_ending_time(10000, 10000));
+
+    let err = instantiate(
+        deps.as_mut(),
+        mock_env_at_timestamp(0),
+        mock_info("deployer", &[]),
+        InstantiateMsg {
+            bond_denom: "1mars".into(),
+            ..msg.clone()
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_denom("1mars", "must start with a letter"));
+
+    let err = instantiate(
+        deps.as_mut(),
+        mock_env_at_timestamp(0),
+        mock_info("deployer", &[]),
+        InstantiateMsg {
+            bond_denom: "u mars".into(),
+            ..msg
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_denom("u mars", "contains invalid characters"));
+}
+
+#[test]
+fn updating_config() {
+    let mut deps = setup_test();
+
+    let update = |ending_time, bond_denom: Option<&str>| SudoMsg::UpdateConfig {
+        bond_denom: bond_denom.map(Into::into),
+        ending_time,
+        min_validators: None,
+        reward_policy: None,
+        unbonding_period: None,
+    };
+
+    // the ending time must be in the future
+    let err =
+        sudo(deps.as_mut(), mock_env_at_timestamp(20000), update(Some(15000), None)).unwrap_err();
+    assert_eq!(err, ContractError::invalid_ending_time(15000, 20000));
+
+    // extend the program
+    sudo(deps.as_mut(), mock_env_at_timestamp(9000), update(Some(30000), None)).unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(20000),
+        mock_info("larry", &[]),
+        ExecuteMsg::Unbond {
+            program: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::ending_time_not_reached(30000, 20000));
+
+    // the denom can't change while tokens are bonded
+    let err = sudo(deps.as_mut(), mock_env(), update(None, Some("uatom"))).unwrap_err();
+    assert_eq!(err, ContractError::BondDenomLocked);
+
+    // setting the same denom is allowed
+    sudo(deps.as_mut(), mock_env(), update(None, Some(BOND_DENOM))).unwrap();
+
+    deps.querier.update_staking(BOND_DENOM, &[], &[]);
+
+    // a named program's budget is held in the denom before it's bonded as well
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::CreateProgram {
+            id: "builders".into(),
+            program: Program {
+                budget: Uint128::new(4000),
+                ending_time: 2_000_000_000,
+                validators: vec![],
+                refund_address: None,
+            },
+        },
+    )
+    .unwrap();
+
+    let err = sudo(deps.as_mut(), mock_env(), update(None, Some("uatom"))).unwrap_err();
+    assert_eq!(err, ContractError::BondDenomLocked);
+
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(4000, BOND_DENOM));
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::CancelProgram {
+            id: "builders".into(),
+        },
+    )
+    .unwrap();
+
+    sudo(deps.as_mut(), mock_env(), update(None, Some("uatom"))).unwrap();
+
+    let cfg: Config =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(cfg.bond_denom, "uatom");
+    assert_eq!(cfg.ending_time, 30000);
+}
+
 #[test]
 fn bonding() {
     let mut deps = setup_test();
@@ -122,7 +281,14 @@ fn bonding() {
     // this simulates governance giving the contract 25M MARS from community pool
     deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(25_000_000_000_000, BOND_DENOM));
 
-    let res = sudo(deps.as_mut(), mock_env(), SudoMsg::Bond {}).unwrap();
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap();
     assert_eq!(
         res.messages,
         vec![
@@ -143,101 +309,1433 @@ fn bonding() {
 }
 
 #[test]
-fn forced_unbonding() {
+fn updating_validators() {
+    let mut deps = setup_test();
+
+    let weighted = |address: &str, weight: u64| WeightedValidator {
+        address: address.into(),
+        weight,
+    };
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![weighted("larry", 1), weighted("jake", 0)],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::zero_weight("jake"));
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![weighted("larry", 1), weighted("larry", 2)],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::duplicate_validator("larry"));
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![weighted("larry", 1), weighted("jeremy", 1)],
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::validator_not_found("jeremy"));
+
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![weighted("larry", 1), weighted("pumpkin", 3)],
+        },
+    )
+    .unwrap();
+
+    let cfg_bytes = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+    let cfg: Config = from_json(cfg_bytes).unwrap();
+    assert_eq!(cfg.validators, vec![weighted("larry", 1), weighted("pumpkin", 3)]);
+}
+
+#[test]
+fn bonding_with_weights() {
     let mut deps = setup_test();
 
-    let res = sudo(deps.as_mut(), mock_env(), SudoMsg::ForceUnbond {}).unwrap();
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![
+                WeightedValidator {
+                    address: "larry".into(),
+                    weight: 1,
+                },
+                WeightedValidator {
+                    address: "jake".into(),
+                    weight: 2,
+                },
+                WeightedValidator {
+                    address: "pumpkin".into(),
+                    weight: 4,
+                },
+            ],
+        },
+    )
+    .unwrap();
+
+    // larry:   10001 * 1 / 7 = 1428 + 1 (remainder)
+    // jake:    10001 * 2 / 7 = 2857 + 1 (remainder)
+    // pumpkin: 10001 * 4 / 7 = 5714
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10001, BOND_DENOM));
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap();
     assert_eq!(
         res.messages,
         vec![
-            SubMsg::new(StakingMsg::Undelegate {
+            SubMsg::new(StakingMsg::Delegate {
                 validator: "larry".into(),
-                amount: coin(3334, BOND_DENOM)
+                amount: coin(1429, BOND_DENOM),
             }),
-            SubMsg::new(StakingMsg::Undelegate {
+            SubMsg::new(StakingMsg::Delegate {
                 validator: "jake".into(),
-                amount: coin(3333, BOND_DENOM)
+                amount: coin(2858, BOND_DENOM),
             }),
-            SubMsg::new(StakingMsg::Undelegate {
+            SubMsg::new(StakingMsg::Delegate {
                 validator: "pumpkin".into(),
-                amount: coin(3333, BOND_DENOM)
-            })
-        ]
+                amount: coin(5714, BOND_DENOM),
+            }),
+        ],
+    );
+
+    // large weights and amounts don't overflow
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![
+                WeightedValidator {
+                    address: "larry".into(),
+                    weight: u64::MAX,
+                },
+                WeightedValidator {
+                    address: "jake".into(),
+                    weight: u64::MAX,
+                },
+            ],
+        },
+    )
+    .unwrap();
+
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10u128.pow(30) + 1, BOND_DENOM));
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "larry".into(),
+                amount: coin(10u128.pow(30) / 2 + 1, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "jake".into(),
+                amount: coin(10u128.pow(30) / 2, BOND_DENOM),
+            }),
+        ],
     );
 }
 
 #[test]
-fn unbonding() {
+fn bonding_with_inactive_validators() {
     let mut deps = setup_test();
 
-    // cannot unbond before the ending time is reached
-    {
-        let err = execute(
-            deps.as_mut(),
-            mock_env_at_timestamp(9999),
-            mock_info("larry", &[]),
-            ExecuteMsg::Unbond {},
-        )
-        .unwrap_err();
-        assert_eq!(err, ContractError::ending_time_not_reached(10000, 9999));
-    }
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![
+                WeightedValidator {
+                    address: "larry".into(),
+                    weight: 1,
+                },
+                WeightedValidator {
+                    address: "jake".into(),
+                    weight: 2,
+                },
+                WeightedValidator {
+                    address: "pumpkin".into(),
+                    weight: 4,
+                },
+            ],
+        },
+    )
+    .unwrap();
 
-    // can unbond after ending time is reached
-    {
-        let res = execute(
-            deps.as_mut(),
-            mock_env_at_timestamp(69420),
-            mock_info("larry", &[]),
-            ExecuteMsg::Unbond {},
-        )
-        .unwrap();
-        assert_eq!(
-            res.messages,
-            vec![
-                SubMsg::new(StakingMsg::Undelegate {
-                    validator: "larry".into(),
-                    amount: coin(3334, BOND_DENOM)
-                }),
-                SubMsg::new(StakingMsg::Undelegate {
-                    validator: "jake".into(),
-                    amount: coin(3333, BOND_DENOM)
-                }),
-                SubMsg::new(StakingMsg::Undelegate {
-                    validator: "pumpkin".into(),
-                    amount: coin(3333, BOND_DENOM)
-                })
-            ]
-        );
-    }
+    // jake is jailed, so it drops out of the active set
+    let validators = ["larry", "pumpkin"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+    deps.querier.update_staking(BOND_DENOM, &validators, &[]);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10000, BOND_DENOM));
+
+    // jake's share goes to the others
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "larry".into(),
+                amount: coin(2000, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "pumpkin".into(),
+                amount: coin(8000, BOND_DENOM),
+            }),
+        ],
+    );
+
+    // fewer active validators than the configured minimum
+    instantiate(
+        deps.as_mut(),
+        mock_env_at_timestamp(0),
+        mock_info("deployer", &[]),
+        InstantiateMsg {
+            bond_denom: BOND_DENOM.into(),
+            ending_time: 10000,
+            validators: vec![],
+            min_validators: 3,
+            reward_policy: RewardPolicy::Restake,
+            reward_withdraw_address: None,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
+        },
+    )
+    .unwrap();
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::no_validators(2, 3));
+
+    // no active validator at all, e.g. on a fresh localnet
+    deps.querier.update_staking(BOND_DENOM, &[], &[]);
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::no_validators(0, 3));
 }
 
 #[test]
-fn refunding() {
+fn rebalancing() {
     let mut deps = setup_test();
 
-    // give the contract some coin balance to be refunded
-    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10000, BOND_DENOM));
+    // delegations already match an even split
+    let err =
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Rebalance {})
+            .unwrap_err();
+    assert_eq!(err, ContractError::NothingToRebalance);
 
-    {
-        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10000, BOND_DENOM));
-        let res =
-            execute(deps.as_mut(), mock_env(), mock_info("larry", &[]), ExecuteMsg::Refund {})
-                .unwrap();
-        assert_eq!(
-            res.messages,
-            vec![SubMsg::new(MarsMsg::FundCommunityPool {
-                amount: coins(10000, BOND_DENOM)
-            })]
-        );
-    }
+    sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::UpdateValidators {
+            validators: vec![
+                WeightedValidator {
+                    address: "larry".into(),
+                    weight: 1,
+                },
+                WeightedValidator {
+                    address: "jake".into(),
+                    weight: 1,
+                },
+                WeightedValidator {
+                    address: "pumpkin".into(),
+                    weight: 2,
+                },
+            ],
+        },
+    )
+    .unwrap();
 
-    // test the case where the contract has no coin balance
-    {
-        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+    // larry:   3334 -> 2500
+    // jake:    3333 -> 2500
+    // pumpkin: 3333 -> 5000
+    let res =
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Rebalance {})
+            .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Redelegate {
+                src_validator: "larry".into(),
+                dst_validator: "pumpkin".into(),
+                amount: coin(834, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Redelegate {
+                src_validator: "jake".into(),
+                dst_validator: "pumpkin".into(),
+                amount: coin(833, BOND_DENOM),
+            }),
+        ],
+    );
 
-        let err =
-            execute(deps.as_mut(), mock_env(), mock_info("larry", &[]), ExecuteMsg::Refund {})
-                .unwrap_err();
-        assert_eq!(err, ContractError::NothingToRefund);
-    }
+    // some of larry's tokens were recently redelegated to it and can't be moved again yet; jake's
+    // tokens are all locked, so jake is skipped
+    let validators = ["larry", "jake", "pumpkin"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+    let delegations = [("larry", 3334, 500), ("jake", 3333, 0), ("pumpkin", 3333, 3333)].map(
+        |(validator, amount, can_redelegate)| FullDelegation {
+            delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+            validator: validator.into(),
+            amount: coin(amount, BOND_DENOM),
+            can_redelegate: coin(can_redelegate, BOND_DENOM),
+            accumulated_rewards: vec![],
+        },
+    );
+    deps.querier.update_staking(BOND_DENOM, &validators, &delegations);
+
+    let res =
+        execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Rebalance {})
+            .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(StakingMsg::Redelegate {
+            src_validator: "larry".into(),
+            dst_validator: "pumpkin".into(),
+            amount: coin(500, BOND_DENOM),
+        })],
+    );
+}
+
+#[test]
+fn harvesting() {
+    let mut deps = setup_test();
+
+    let err = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Harvest {})
+        .unwrap_err();
+    assert_eq!(err, ContractError::NothingToHarvest);
+
+    let validators = ["larry", "jake", "pumpkin"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+    let delegations = [
+        ("larry", 3334, vec![coin(100, BOND_DENOM)]),
+        ("jake", 3333, vec![]),
+        ("pumpkin", 3333, vec![coin(50, BOND_DENOM), coin(5, "uatom")]),
+    ]
+    .map(|(validator, amount, accumulated_rewards)| FullDelegation {
+        delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+        validator: validator.into(),
+        amount: coin(amount, BOND_DENOM),
+        can_redelegate: coin(amount, BOND_DENOM),
+        accumulated_rewards,
+    });
+    deps.querier.update_staking(BOND_DENOM, &validators, &delegations);
+
+    // rewards in the bond denom are restaked; others are left in the contract
+    let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Harvest {})
+        .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+                validator: "larry".into(),
+            }),
+            SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+                validator: "pumpkin".into(),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "larry".into(),
+                amount: coin(50, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "jake".into(),
+                amount: coin(50, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "pumpkin".into(),
+                amount: coin(50, BOND_DENOM),
+            }),
+        ],
+    );
+
+    // switch to donating rewards to the community pool
+    instantiate(
+        deps.as_mut(),
+        mock_env_at_timestamp(0),
+        mock_info("deployer", &[]),
+        InstantiateMsg {
+            bond_denom: BOND_DENOM.into(),
+            ending_time: 10000,
+            validators: vec![],
+            min_validators: 0,
+            reward_policy: RewardPolicy::CommunityPool,
+            reward_withdraw_address: None,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
+        },
+    )
+    .unwrap();
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Harvest {})
+        .unwrap();
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(MarsMsg::FundCommunityPool {
+            amount: vec![coin(5, "uatom"), coin(150, BOND_DENOM)],
+        }),
+    );
+
+    // switch to sending rewards to a treasury
+    instantiate(
+        deps.as_mut(),
+        mock_env_at_timestamp(0),
+        mock_info("deployer", &[]),
+        InstantiateMsg {
+            bond_denom: BOND_DENOM.into(),
+            ending_time: 10000,
+            validators: vec![],
+            min_validators: 0,
+            reward_policy: RewardPolicy::Send {
+                recipient: "treasury".into(),
+            },
+            reward_withdraw_address: None,
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
+        },
+    )
+    .unwrap();
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Harvest {})
+        .unwrap();
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: "treasury".into(),
+            amount: vec![coin(5, "uatom"), coin(150, BOND_DENOM)],
+        }),
+    );
+
+    // rewards are tracked across harvests
+    let res: RewardsResponse =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Rewards {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        RewardsResponse {
+            total_harvested: vec![coin(15, "uatom"), coin(450, BOND_DENOM)],
+        },
+    );
+}
+
+#[test]
+fn setting_reward_withdraw_address() {
+    let mut deps = setup_test();
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::SetRewardWithdrawAddress {
+            address: Some("treasury".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(DistributionMsg::SetWithdrawAddress {
+            address: "treasury".into(),
+        })],
+    );
+
+    let cfg: Config =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(cfg.reward_withdraw_address, Some("treasury".into()));
+
+    // rewards go straight to the treasury, so harvesting only claims them
+    let validators = ["larry"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+    let delegations = [FullDelegation {
+        delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+        validator: "larry".into(),
+        amount: coin(10000, BOND_DENOM),
+        can_redelegate: coin(10000, BOND_DENOM),
+        accumulated_rewards: vec![coin(100, BOND_DENOM)],
+    }];
+    deps.querier.update_staking(BOND_DENOM, &validators, &delegations);
+
+    let res = execute(deps.as_mut(), mock_env(), mock_info("anyone", &[]), ExecuteMsg::Harvest {})
+        .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(DistributionMsg::WithdrawDelegatorReward {
+            validator: "larry".into(),
+        })],
+    );
+
+    // reset to the contract itself
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::SetRewardWithdrawAddress {
+            address: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(DistributionMsg::SetWithdrawAddress {
+            address: MOCK_CONTRACT_ADDR.into(),
+        })],
+    );
+
+    let cfg: Config =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+    assert_eq!(cfg.reward_withdraw_address, None);
+}
+
+#[test]
+fn forced_unbonding() {
+    let mut deps = setup_test();
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::ForceUnbond {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "larry".into(),
+                amount: coin(3334, BOND_DENOM)
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "jake".into(),
+                amount: coin(3333, BOND_DENOM)
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "pumpkin".into(),
+                amount: coin(3333, BOND_DENOM)
+            })
+        ]
+    );
+}
+
+#[test]
+fn unbonding() {
+    let mut deps = setup_test();
+
+    // cannot unbond before the ending time is reached
+    {
+        let err = execute(
+            deps.as_mut(),
+            mock_env_at_timestamp(9999),
+            mock_info("larry", &[]),
+            ExecuteMsg::Unbond {
+                program: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::ending_time_not_reached(10000, 9999));
+    }
+
+    // can unbond after ending time is reached
+    {
+        let res = execute(
+            deps.as_mut(),
+            mock_env_at_timestamp(69420),
+            mock_info("larry", &[]),
+            ExecuteMsg::Unbond {
+                program: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(StakingMsg::Undelegate {
+                    validator: "larry".into(),
+                    amount: coin(3334, BOND_DENOM)
+                }),
+                SubMsg::new(StakingMsg::Undelegate {
+                    validator: "jake".into(),
+                    amount: coin(3333, BOND_DENOM)
+                }),
+                SubMsg::new(StakingMsg::Undelegate {
+                    validator: "pumpkin".into(),
+                    amount: coin(3333, BOND_DENOM)
+                })
+            ]
+        );
+    }
+}
+
+#[test]
+fn refunding() {
+    let mut deps = setup_test();
+
+    // give the contract some coin balance to be refunded
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10000, BOND_DENOM));
+
+    {
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10000, BOND_DENOM));
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("larry", &[]),
+            ExecuteMsg::Refund {
+                program: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(MarsMsg::FundCommunityPool {
+                amount: coins(10000, BOND_DENOM)
+            })]
+        );
+    }
+
+    // test the case where the contract has no coin balance
+    {
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("larry", &[]),
+            ExecuteMsg::Refund {
+                program: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToRefund);
+    }
+}
+
+#[test]
+fn refunding_after_unbonding() {
+    let mut deps = setup_test();
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(69420),
+        mock_info("larry", &[]),
+        ExecuteMsg::Unbond {
+            program: None,
+        },
+    )
+    .unwrap();
+    let completion_time = 69420 + DEFAULT_UNBONDING_PERIOD;
+    assert!(res.attributes.contains(&attr("completion_time", completion_time.to_string())));
+
+    // the mock staking module doesn't process undelegations, so delegations are unchanged
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(12, BOND_DENOM));
+
+    let status: StatusResponse =
+        from_json(query(deps.as_ref(), mock_env_at_timestamp(69420), QueryMsg::Status {}).unwrap())
+            .unwrap();
+    assert_eq!(
+        status,
+        StatusResponse {
+            bonded: Uint128::new(10000),
+            unbonding: Uint128::new(10000),
+            liquid: Uint128::new(12),
+            batches: vec![UnbondingBatch {
+                id: 1,
+                program: None,
+                amount: Uint128::new(10000),
+                completion_time,
+            }],
+        },
+    );
+
+    // cannot refund before the unbonding completes
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(completion_time - 1),
+        mock_info("larry", &[]),
+        ExecuteMsg::Refund {
+            program: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::unbonding_not_complete(completion_time, completion_time - 1));
+
+    // once completed, the tokens are back in the contract's balance
+    deps.querier.update_staking(BOND_DENOM, &[], &[]);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10012, BOND_DENOM));
+
+    let status: StatusResponse = from_json(
+        query(deps.as_ref(), mock_env_at_timestamp(completion_time), QueryMsg::Status {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(status.bonded, Uint128::zero());
+    assert_eq!(status.unbonding, Uint128::zero());
+    assert_eq!(status.liquid, Uint128::new(10012));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(completion_time),
+        mock_info("larry", &[]),
+        ExecuteMsg::Refund {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(MarsMsg::FundCommunityPool {
+            amount: coins(10012, BOND_DENOM)
+        })]
+    );
+
+    // refunded batches are cleared
+    let status: StatusResponse = from_json(
+        query(deps.as_ref(), mock_env_at_timestamp(completion_time), QueryMsg::Status {}).unwrap(),
+    )
+    .unwrap();
+    assert!(status.batches.is_empty());
+}
+
+#[test]
+fn querying_state() {
+    let mut deps = setup_test();
+
+    let validators = ["larry", "jake"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+
+    // nothing delegated yet
+    deps.querier.update_staking(BOND_DENOM, &validators, &[]);
+
+    let phase: Phase =
+        from_json(query(deps.as_ref(), mock_env_at_timestamp(100), QueryMsg::Phase {}).unwrap())
+            .unwrap();
+    assert_eq!(phase, Phase::Bonding);
+
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR, vec![coin(5, "uatom"), coin(10000, BOND_DENOM)]);
+
+    let balances: Vec<Coin> =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Balances {}).unwrap()).unwrap();
+    assert_eq!(balances, vec![coin(5, "uatom"), coin(10000, BOND_DENOM)]);
+
+    sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(100),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap();
+
+    // the delegations are made
+    let delegations = [("larry", vec![coin(20, BOND_DENOM)]), ("jake", vec![])].map(
+        |(validator, accumulated_rewards)| FullDelegation {
+            delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+            validator: validator.into(),
+            amount: coin(5000, BOND_DENOM),
+            can_redelegate: coin(5000, BOND_DENOM),
+            accumulated_rewards,
+        },
+    );
+    deps.querier.update_staking(BOND_DENOM, &validators, &delegations);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(5, "uatom"));
+
+    let res: Vec<DelegationResponse> =
+        from_json(query(deps.as_ref(), mock_env(), QueryMsg::Delegations {}).unwrap()).unwrap();
+    assert_eq!(
+        res,
+        vec![
+            DelegationResponse {
+                validator: "larry".into(),
+                amount: coin(5000, BOND_DENOM),
+                pending_rewards: vec![coin(20, BOND_DENOM)],
+            },
+            DelegationResponse {
+                validator: "jake".into(),
+                amount: coin(5000, BOND_DENOM),
+                pending_rewards: vec![],
+            },
+        ],
+    );
+
+    let phase: Phase =
+        from_json(query(deps.as_ref(), mock_env_at_timestamp(9999), QueryMsg::Phase {}).unwrap())
+            .unwrap();
+    assert_eq!(phase, Phase::Active);
+
+    let phase: Phase =
+        from_json(query(deps.as_ref(), mock_env_at_timestamp(10000), QueryMsg::Phase {}).unwrap())
+            .unwrap();
+    assert_eq!(phase, Phase::Ended);
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        mock_info("larry", &[]),
+        ExecuteMsg::Unbond {
+            program: None,
+        },
+    )
+    .unwrap();
+
+    let phase: Phase =
+        from_json(query(deps.as_ref(), mock_env_at_timestamp(10000), QueryMsg::Phase {}).unwrap())
+            .unwrap();
+    assert_eq!(phase, Phase::Unbonding);
+
+    // the unbonding completes
+    let completion_time = 10000 + DEFAULT_UNBONDING_PERIOD;
+    deps.querier.update_staking(BOND_DENOM, &validators, &[]);
+    deps.querier
+        .update_balance(MOCK_CONTRACT_ADDR, vec![coin(5, "uatom"), coin(10000, BOND_DENOM)]);
+
+    let phase: Phase = from_json(
+        query(deps.as_ref(), mock_env_at_timestamp(completion_time), QueryMsg::Phase {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(phase, Phase::Ended);
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(completion_time),
+        mock_info("larry", &[]),
+        ExecuteMsg::Refund {
+            program: None,
+        },
+    )
+    .unwrap();
+
+    let phase: Phase = from_json(
+        query(deps.as_ref(), mock_env_at_timestamp(completion_time), QueryMsg::Phase {}).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(phase, Phase::Refunded);
+
+    let history_query = |start_after, start_before, order| QueryMsg::History {
+        start_after,
+        start_before,
+        limit: Some(2),
+        order,
+    };
+
+    let res: Paginated<HistoryEntry, u64> =
+        from_json(query(deps.as_ref(), mock_env(), history_query(None, None, None)).unwrap())
+            .unwrap();
+    assert_eq!(
+        res,
+        Paginated {
+            data: vec![
+                HistoryEntry {
+                    id: 1,
+                    program: None,
+                    action: Action::Bond,
+                    amount: coins(10000, BOND_DENOM),
+                    timestamp: 100,
+                },
+                HistoryEntry {
+                    id: 2,
+                    program: None,
+                    action: Action::Unbond,
+                    amount: coins(10000, BOND_DENOM),
+                    timestamp: 10000,
+                },
+            ],
+            next: Some(2),
+            total: 3,
+        },
+    );
+
+    let res: Paginated<HistoryEntry, u64> =
+        from_json(query(deps.as_ref(), mock_env(), history_query(res.next, None, None)).unwrap())
+            .unwrap();
+    assert_eq!(
+        res.data,
+        vec![HistoryEntry {
+            id: 3,
+            program: None,
+            action: Action::Refund,
+            amount: vec![coin(5, "uatom"), coin(10000, BOND_DENOM)],
+            timestamp: completion_time,
+        }],
+    );
+    assert_eq!(res.next, None);
+
+    let res: Paginated<HistoryEntry, u64> = from_json(
+        query(deps.as_ref(), mock_env(), history_query(None, None, Some(OrderBy::Descending)))
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.data.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![3, 2]);
+}
+
+#[test]
+fn multiple_programs() {
+    let mut deps = setup_test();
+
+    let program = Program {
+        budget: Uint128::new(4000),
+        ending_time: 20000,
+        validators: vec![WeightedValidator {
+            address: "larry".into(),
+            weight: 1,
+        }],
+        refund_address: Some("treasury".into()),
+    };
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(20000),
+        SudoMsg::CreateProgram {
+            id: "builders".into(),
+            program: program.clone(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_ending_time(20000, 20000));
+
+    sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::CreateProgram {
+            id: "builders".into(),
+            program: program.clone(),
+        },
+    )
+    .unwrap();
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::CreateProgram {
+            id: "builders".into(),
+            program: program.clone(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::program_exists("builders"));
+
+    // the program's budget is set aside, so the main program only bonds the rest
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(6000, BOND_DENOM));
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "larry".into(),
+                amount: coin(667, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "jake".into(),
+                amount: coin(667, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "pumpkin".into(),
+                amount: coin(666, BOND_DENOM),
+            }),
+        ],
+    );
+
+    let validators = ["larry", "jake", "pumpkin"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+    deps.querier.update_staking(
+        BOND_DENOM,
+        &validators,
+        &full_delegations(&[("larry", 4001), ("jake", 4000), ("pumpkin", 3999)]),
+    );
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(StakingMsg::Delegate {
+            validator: "larry".into(),
+            amount: coin(4000, BOND_DENOM),
+        })],
+    );
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_program_phase("builders", &Phase::Active));
+
+    // the staking module sees a single delegation per validator
+    deps.querier.update_staking(
+        BOND_DENOM,
+        &validators,
+        &full_delegations(&[("larry", 8001), ("jake", 4000), ("pumpkin", 3999)]),
+    );
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+    let res: ProgramResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env_at_timestamp(15000),
+            QueryMsg::Program {
+                id: "builders".into(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        ProgramResponse {
+            id: "builders".into(),
+            program: program.clone(),
+            phase: Phase::Active,
+            delegations: vec![ProgramDelegation {
+                validator: "larry".into(),
+                amount: Uint128::new(4000),
+            }],
+        },
+    );
+
+    // each program ends at its own time
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(15000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Unbond {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::ending_time_not_reached(20000, 15000));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(15000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Unbond {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "larry".into(),
+                amount: coin(4001, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "jake".into(),
+                amount: coin(4000, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "pumpkin".into(),
+                amount: coin(3999, BOND_DENOM),
+            }),
+        ],
+    );
+
+    deps.querier.update_staking(BOND_DENOM, &validators, &full_delegations(&[("larry", 4000)]));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(20000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Unbond {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(StakingMsg::Undelegate {
+            validator: "larry".into(),
+            amount: coin(4000, BOND_DENOM),
+        })],
+    );
+
+    // both unbondings complete
+    let completion_time = 20000 + DEFAULT_UNBONDING_PERIOD;
+    deps.querier.update_staking(BOND_DENOM, &validators, &[]);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(16000, BOND_DENOM));
+
+    // the main program's refund leaves the program's budget alone
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(completion_time),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Refund {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(MarsMsg::FundCommunityPool {
+            amount: coins(12000, BOND_DENOM),
+        })],
+    );
+
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(4000, BOND_DENOM));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(completion_time),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Refund {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "treasury".into(),
+            amount: coins(4000, BOND_DENOM),
+        })],
+    );
+
+    let res: Paginated<ProgramResponse> = from_json(
+        query(
+            deps.as_ref(),
+            mock_env_at_timestamp(completion_time),
+            QueryMsg::Programs {
+                start_after: None,
+                start_before: None,
+                limit: None,
+                order: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.total, 1);
+    assert_eq!(res.data[0].phase, Phase::Refunded);
+    assert!(res.data[0].delegations.is_empty());
+}
+
+#[test]
+fn cancel_program() {
+    let mut deps = setup_test();
+
+    let program = Program {
+        budget: Uint128::new(4000),
+        ending_time: 20000,
+        validators: vec![WeightedValidator {
+            address: "larry".into(),
+            weight: 1,
+        }],
+        refund_address: None,
+    };
+
+    for id in ["builders", "grants"] {
+        sudo(
+            deps.as_mut(),
+            mock_env_at_timestamp(10000),
+            SudoMsg::CreateProgram {
+                id: id.into(),
+                program: program.clone(),
+            },
+        )
+        .unwrap();
+    }
+
+    // the contract is short on tokens
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(6000, BOND_DENOM));
+
+    // a program that hasn't been bonded yet can't be refunded by just anyone
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Refund {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_program_phase("builders", &Phase::Bonding));
+
+    // the other program's budget is never paid out
+    let res = sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::CancelProgram {
+            id: "builders".into(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(MarsMsg::FundCommunityPool {
+            amount: coins(2000, BOND_DENOM),
+        })],
+    );
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::CancelProgram {
+            id: "builders".into(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_program_phase("builders", &Phase::Refunded));
+
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(4000, BOND_DENOM));
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env(),
+        SudoMsg::Bond {
+            program: Some("grants".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+
+    let err = sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::CancelProgram {
+            id: "grants".into(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::invalid_program_phase("grants", &Phase::Active));
+}
+
+#[test]
+fn slashing_programs() {
+    let mut deps = setup_test();
+
+    let validators = ["larry", "jake", "pumpkin", "dave"].map(|address| Validator {
+        address: address.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::zero(),
+        max_change_rate: Decimal::zero(),
+    });
+    deps.querier.update_staking(
+        BOND_DENOM,
+        &validators,
+        &full_delegations(&[("larry", 3334), ("jake", 3333), ("pumpkin", 3333)]),
+    );
+
+    // the program shares larry with the main program, and is the only one delegating to dave
+    let program = Program {
+        budget: Uint128::new(4000),
+        ending_time: 20000,
+        validators: vec![
+            WeightedValidator {
+                address: "larry".into(),
+                weight: 1,
+            },
+            WeightedValidator {
+                address: "dave".into(),
+                weight: 1,
+            },
+        ],
+        refund_address: None,
+    };
+
+    sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::CreateProgram {
+            id: "builders".into(),
+            program: program.clone(),
+        },
+    )
+    .unwrap();
+
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(4000, BOND_DENOM));
+
+    let res = sudo(
+        deps.as_mut(),
+        mock_env_at_timestamp(10000),
+        SudoMsg::Bond {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "larry".into(),
+                amount: coin(2000, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Delegate {
+                validator: "dave".into(),
+                amount: coin(2000, BOND_DENOM),
+            }),
+        ],
+    );
+
+    // every validator is slashed by 10%
+    deps.querier.update_staking(
+        BOND_DENOM,
+        &validators,
+        &full_delegations(&[("larry", 4800), ("jake", 3000), ("pumpkin", 3000), ("dave", 1800)]),
+    );
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+    // the slash on larry is shared pro rata with the main program
+    let res: ProgramResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env_at_timestamp(15000),
+            QueryMsg::Program {
+                id: "builders".into(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        ProgramResponse {
+            id: "builders".into(),
+            program,
+            phase: Phase::Active,
+            delegations: vec![
+                ProgramDelegation {
+                    validator: "dave".into(),
+                    amount: Uint128::new(1800),
+                },
+                ProgramDelegation {
+                    validator: "larry".into(),
+                    amount: Uint128::new(1799),
+                },
+            ],
+        },
+    );
+
+    // the program only undelegates what is left of its delegations
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(20000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Unbond {
+            program: Some("builders".into()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "dave".into(),
+                amount: coin(1800, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "larry".into(),
+                amount: coin(1799, BOND_DENOM),
+            }),
+        ],
+    );
+
+    deps.querier.update_staking(
+        BOND_DENOM,
+        &validators,
+        &full_delegations(&[("larry", 3001), ("jake", 3000), ("pumpkin", 3000)]),
+    );
+
+    // the main program only bears its own part of the slash on larry, plus the rounding dust
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(20000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::Unbond {
+            program: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "larry".into(),
+                amount: coin(3001, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "jake".into(),
+                amount: coin(3000, BOND_DENOM),
+            }),
+            SubMsg::new(StakingMsg::Undelegate {
+                validator: "pumpkin".into(),
+                amount: coin(3000, BOND_DENOM),
+            }),
+        ],
+    );
 }
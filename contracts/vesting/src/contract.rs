@@ -1,30 +1,39 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coins, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Deps, DepsMut, Env, MessageInfo,
-    Order, Response, Uint128,
+    coins, to_json_binary, Addr, BankMsg, Binary, BlockInfo, CosmosMsg, Deps, DepsMut, Empty, Env,
+    MessageInfo, Order, Response, StdResult, Storage, Timestamp, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
 use cw_storage_plus::Bound;
 use cw_utils::must_pay;
+use mars_pagination as pagination;
 
 use crate::{
+    cw721::{Cw721ExecuteMsg, Cw721QueryMsg, OwnerOfResponse},
     error::{Error, Result},
-    helpers::{compute_position_response, compute_withdrawable},
-    migrations::{v1_1_0, v1_1_1, v1_1_2},
+    helpers::{
+        assert_denom_unchanged, assert_role, assert_valid_schedule, compute_liabilities,
+        compute_position_response, compute_status, compute_withdrawable, merge, split, terminate,
+    },
+    migrations::{v1_1_0, v1_1_1, v1_1_2, v1_2_0},
     msg::{
-        Config, ExecuteMsg, MigrateMsg, Position, PositionResponse, QueryMsg, Schedule,
-        VotingPowerResponse,
+        Config, EmergencyRelease, EmergencyState, ExecuteMsg, MigrateMsg, OrderBy, Paginated,
+        PendingConfig, Position, PositionFilter, PositionResponse, PositionStatus, QueryMsg, Role,
+        RolesResponse, Schedule, SolvencyResponse, VotingPowerResponse, WithdrawAuthorization,
+        WithdrawAuthorizationResponse,
+    },
+    state::{
+        AUTO_DISTRIBUTE, CONFIG, DISTRIBUTE_CURSOR, EMERGENCY, EMERGENCY_CURSOR, PENDING_CONFIG,
+        POSITIONS, ROLES, STATUS_FULLY_WITHDRAWN, STATUS_TERMINATED, STATUS_VESTING,
+        TOKENIZED_POSITIONS, WITHDRAW_AUTHORIZATIONS, WITHDRAW_ENABLED,
     },
-    state::{CONFIG, POSITIONS, WITHDRAW_ENABLED},
+    stream,
 };
 
 pub const CONTRACT_NAME: &str = "crates.io:mars-vesting";
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-const MAX_LIMIT: u32 = 30;
-const DEFAULT_LIMIT: u32 = 10;
-
 //--------------------------------------------------------------------------------------------------
 // Instantiation
 //--------------------------------------------------------------------------------------------------
@@ -39,6 +48,7 @@ pub fn instantiate(
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
     let cfg = cfg.check(deps.api)?;
+    assert_valid_schedule(&cfg.unlock_schedule)?;
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::new())
@@ -50,11 +60,28 @@ pub fn instantiate(
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> Result<Response> {
+    // following an emergency shutdown, the only thing users can do is to withdraw, or to close
+    // their payment streams
+    if EMERGENCY.exists(deps.storage)
+        && !matches!(
+            msg,
+            ExecuteMsg::EmergencyRecover { .. }
+                | ExecuteMsg::EmergencyWithdraw {}
+                | ExecuteMsg::EmergencyWithdrawToken { .. }
+                | ExecuteMsg::CancelStream { .. }
+                | ExecuteMsg::WithdrawStream { .. }
+        )
+    {
+        return Err(Error::Shutdown);
+    }
+
     let api = deps.api;
     match msg {
-        ExecuteMsg::UpdateConfig {
+        ExecuteMsg::ProposeConfig {
             new_cfg,
-        } => update_config(deps, info, new_cfg),
+        } => propose_config(deps, env, info, new_cfg),
+        ExecuteMsg::ExecuteConfig {} => execute_config(deps, env),
+        ExecuteMsg::CancelConfig {} => cancel_config(deps, info),
         ExecuteMsg::CreatePosition {
             user,
             vest_schedule,
@@ -62,26 +89,159 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> R
         ExecuteMsg::TerminatePosition {
             user,
         } => terminate_position(deps, env, info, api.addr_validate(&user)?),
-        ExecuteMsg::Withdraw {} => withdraw(deps, env.block.time.seconds(), info.sender),
+        ExecuteMsg::Withdraw {} => withdraw(deps, &env.block, info.sender),
+        ExecuteMsg::WithdrawToken {
+            token_id,
+        } => withdraw_token(deps, &env.block, info.sender, token_id),
+        ExecuteMsg::GrantWithdrawAuthorization {
+            operator,
+            expiry,
+            max_amount,
+            recipient,
+        } => {
+            let authorization = WithdrawAuthorization {
+                expiry,
+                max_amount,
+                recipient: recipient.map(|addr| api.addr_validate(&addr)).transpose()?,
+            };
+            grant_withdraw_authorization(deps, info, api.addr_validate(&operator)?, authorization)
+        }
+        ExecuteMsg::RevokeWithdrawAuthorization {
+            operator,
+        } => revoke_withdraw_authorization(deps, info, api.addr_validate(&operator)?),
+        ExecuteMsg::WithdrawOnBehalf {
+            user,
+        } => withdraw_on_behalf(deps, &env.block, info.sender, api.addr_validate(&user)?),
+        ExecuteMsg::SplitPosition {
+            amount,
+            new_user,
+        } => split_position(deps, info, amount, api.addr_validate(&new_user)?),
+        ExecuteMsg::MergePositions {
+            into,
+        } => merge_positions(deps, info, api.addr_validate(&into)?),
+        ExecuteMsg::SetAutoDistribute {
+            enabled,
+        } => set_auto_distribute(deps, info, enabled),
+        ExecuteMsg::Distribute {
+            start_after,
+            limit,
+        } => {
+            let start_after = start_after.map(|addr| api.addr_validate(&addr)).transpose()?;
+            distribute(deps, &env.block, start_after, limit)
+        }
+        ExecuteMsg::CreateStream {
+            recipient,
+            rate,
+        } => stream::create_stream(deps, env, info, api.addr_validate(&recipient)?, rate),
+        ExecuteMsg::TopUpStream {
+            id,
+        } => stream::top_up_stream(deps, env, info, id),
+        ExecuteMsg::SetStreamPaused {
+            id,
+            paused,
+        } => stream::set_stream_paused(deps, env, info, id, paused),
+        ExecuteMsg::CancelStream {
+            id,
+        } => stream::cancel_stream(deps, env, info, id),
+        ExecuteMsg::WithdrawStream {
+            id,
+        } => stream::withdraw_stream(deps, env, info, id),
+        ExecuteMsg::GrantRole {
+            user,
+            role,
+        } => grant_role(deps, info, api.addr_validate(&user)?, role),
+        ExecuteMsg::RevokeRole {
+            user,
+            role,
+        } => revoke_role(deps, info, api.addr_validate(&user)?, role),
+        ExecuteMsg::SetWithdrawEnabled {
+            enabled,
+        } => set_withdraw_enabled(deps, info, enabled),
+        ExecuteMsg::SweepSurplus {
+            recipient,
+        } => sweep_surplus(deps, env, info, api.addr_validate(&recipient)?),
+        ExecuteMsg::EmergencyShutdown {
+            recovery_addr,
+        } => emergency_shutdown(deps, env, info, api.addr_validate(&recovery_addr)?),
+        ExecuteMsg::EmergencyRecover {
+            limit,
+        } => emergency_recover(deps, &env.block, limit),
+        ExecuteMsg::EmergencyWithdraw {} => emergency_withdraw(deps, &env.block, info.sender),
+        ExecuteMsg::EmergencyWithdrawToken {
+            token_id,
+        } => emergency_withdraw_token(deps, &env.block, info.sender, token_id),
     }
 }
 
-pub fn update_config(
+pub fn propose_config(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     new_cfg: Config<String>,
 ) -> Result<Response> {
     let cfg = CONFIG.load(deps.storage)?;
 
-    // only owner can update config
-    if info.sender != cfg.owner {
-        return Err(Error::NotOwner);
-    }
+    // only admins can propose config changes
+    assert_role(deps.storage, &cfg, &info.sender, Role::Admin)?;
+
+    // the delay is determined by the current config, so that a proposal can't shorten its own
+    // timelock
+    let executable_at = env.block.time.seconds() + cfg.timelock;
 
     let new_cfg = new_cfg.check(deps.api)?;
-    CONFIG.save(deps.storage, &new_cfg)?;
+    assert_valid_schedule(&new_cfg.unlock_schedule)?;
+    assert_denom_unchanged(deps.storage, &cfg, &new_cfg)?;
 
-    Ok(Response::new().add_attribute("action", "mars/vesting/update_config"))
+    PENDING_CONFIG.save(
+        deps.storage,
+        &PendingConfig {
+            new_cfg,
+            executable_at,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/propose_config")
+        .add_attribute("executable_at", executable_at.to_string()))
+}
+
+pub fn execute_config(deps: DepsMut, env: Env) -> Result<Response> {
+    let current_time = env.block.time.seconds();
+
+    let Some(pending) = PENDING_CONFIG.may_load(deps.storage)? else {
+        return Err(Error::NoPendingConfig);
+    };
+
+    if current_time < pending.executable_at {
+        return Err(Error::TimelockNotElapsed {
+            executable_at: pending.executable_at,
+            current_time,
+        });
+    }
+
+    // positions may have been created since the change was proposed
+    let cfg = CONFIG.load(deps.storage)?;
+    assert_denom_unchanged(deps.storage, &cfg, &pending.new_cfg)?;
+
+    CONFIG.save(deps.storage, &pending.new_cfg)?;
+    PENDING_CONFIG.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "mars/vesting/execute_config"))
+}
+
+pub fn cancel_config(deps: DepsMut, info: MessageInfo) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only admins can cancel config changes
+    assert_role(deps.storage, &cfg, &info.sender, Role::Admin)?;
+
+    if !PENDING_CONFIG.exists(deps.storage) {
+        return Err(Error::NoPendingConfig);
+    }
+
+    PENDING_CONFIG.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "mars/vesting/cancel_config"))
 }
 
 pub fn create_position(
@@ -92,10 +252,10 @@ pub fn create_position(
 ) -> Result<Response> {
     let cfg = CONFIG.load(deps.storage)?;
 
-    // only owner can create allocations
-    if info.sender != cfg.owner {
-        return Err(Error::NotOwner);
-    }
+    // only creators can create allocations
+    assert_role(deps.storage, &cfg, &info.sender, Role::Creator)?;
+
+    assert_valid_schedule(&vest_schedule)?;
 
     let total = must_pay(&info, &cfg.denom)?;
 
@@ -107,10 +267,29 @@ pub fn create_position(
             total,
             vest_schedule: vest_schedule.clone(),
             withdrawn: Uint128::zero(),
+            terminated: false,
+            held_back: Uint128::zero(),
         })
     })?;
 
+    // if positions are to be tokenized, mint an NFT representing this position to the user
+    let mut msgs = vec![];
+    if let Some(nft_contract) = cfg.nft_contract {
+        TOKENIZED_POSITIONS.save(deps.storage, &user_addr, &nft_contract)?;
+        msgs.push(WasmMsg::Execute {
+            contract_addr: nft_contract.into(),
+            msg: to_json_binary(&Cw721ExecuteMsg::Mint {
+                token_id: user_addr.to_string(),
+                owner: user_addr.to_string(),
+                token_uri: None,
+                extension: None,
+            })?,
+            funds: vec![],
+        });
+    }
+
     Ok(Response::new()
+        .add_messages(msgs)
         .add_attribute("action", "mars/vesting/position_created")
         .add_attribute("user", user_addr)
         .add_attribute("total", total)
@@ -126,29 +305,16 @@ pub fn terminate_position(
     user_addr: Addr,
 ) -> Result<Response> {
     let cfg = CONFIG.load(deps.storage)?;
-    let current_time = env.block.time.seconds();
 
-    // only owner can terminate allocations
-    if info.sender != cfg.owner {
-        return Err(Error::NotOwner);
-    }
+    // only terminators can terminate allocations
+    assert_role(deps.storage, &cfg, &info.sender, Role::Terminator)?;
 
     let mut position = POSITIONS.load(deps.storage, &user_addr)?;
 
-    let (vested, _, _) = compute_withdrawable(
-        current_time,
-        position.total,
-        position.withdrawn,
-        &position.vest_schedule,
-        &cfg.unlock_schedule,
-    );
-
+    // set position total amount to be the vested amount so far, and vesting end time to now;
     // unvested tokens are to be reclaimed by the owner
-    let reclaim = position.total - vested;
-
-    // set position total amount to be the vested amount so far, and vesting end time to now
-    position.total = vested;
-    position.vest_schedule.duration = current_time - position.vest_schedule.start_time;
+    let reclaim = terminate(&env.block, &mut position, &cfg.unlock_schedule);
+    let vested = position.total;
     POSITIONS.save(deps.storage, &user_addr, &position)?;
 
     Ok(Response::new()
@@ -162,41 +328,645 @@ pub fn terminate_position(
         .add_attribute("relaimed", reclaim))
 }
 
-pub fn withdraw(deps: DepsMut, time: u64, user_addr: Addr) -> Result<Response> {
+pub fn withdraw(deps: DepsMut, block: &BlockInfo, user_addr: Addr) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    assert_not_tokenized(deps.storage, &user_addr)?;
+
+    let withdrawable = withdraw_position(deps.storage, block, &cfg, &user_addr, None)?;
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: user_addr.to_string(),
+            amount: coins(withdrawable.u128(), cfg.denom),
+        }))
+        .add_attribute("action", "mars/vesting/withdraw")
+        .add_attribute("user", user_addr)
+        .add_attribute("timestamp", block.time.seconds().to_string())
+        .add_attribute("withdrawable", withdrawable))
+}
+
+pub fn withdraw_token(
+    deps: DepsMut,
+    block: &BlockInfo,
+    sender_addr: Addr,
+    token_id: String,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let user_addr = assert_token_owner(deps.as_ref(), &sender_addr, token_id)?;
+
+    let withdrawable = withdraw_position(deps.storage, block, &cfg, &user_addr, None)?;
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: sender_addr.to_string(),
+            amount: coins(withdrawable.u128(), cfg.denom),
+        }))
+        .add_attribute("action", "mars/vesting/withdraw_token")
+        .add_attribute("user", user_addr)
+        .add_attribute("owner", sender_addr)
+        .add_attribute("timestamp", block.time.seconds().to_string())
+        .add_attribute("withdrawable", withdrawable))
+}
+
+pub fn grant_withdraw_authorization(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator_addr: Addr,
+    authorization: WithdrawAuthorization,
+) -> Result<Response> {
+    WITHDRAW_AUTHORIZATIONS.save(deps.storage, (&info.sender, &operator_addr), &authorization)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/grant_withdraw_authorization")
+        .add_attribute("user", info.sender)
+        .add_attribute("operator", operator_addr))
+}
+
+pub fn revoke_withdraw_authorization(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator_addr: Addr,
+) -> Result<Response> {
+    WITHDRAW_AUTHORIZATIONS.remove(deps.storage, (&info.sender, &operator_addr));
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/revoke_withdraw_authorization")
+        .add_attribute("user", info.sender)
+        .add_attribute("operator", operator_addr))
+}
+
+pub fn withdraw_on_behalf(
+    deps: DepsMut,
+    block: &BlockInfo,
+    operator_addr: Addr,
+    user_addr: Addr,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    assert_not_tokenized(deps.storage, &user_addr)?;
+
+    let key = (&user_addr, &operator_addr);
+    let mut authorization = match WITHDRAW_AUTHORIZATIONS.may_load(deps.storage, key)? {
+        Some(authorization) if !authorization.is_expired(block.time.seconds()) => authorization,
+        _ => return Err(Error::NotAuthorized),
+    };
+
+    let withdrawable =
+        withdraw_position(deps.storage, block, &cfg, &user_addr, authorization.max_amount)?;
+
+    // deduct the withdrawn amount from the operator's allowance, if capped
+    if let Some(max_amount) = authorization.max_amount {
+        let remaining = max_amount - withdrawable;
+        if remaining.is_zero() {
+            WITHDRAW_AUTHORIZATIONS.remove(deps.storage, key);
+        } else {
+            authorization.max_amount = Some(remaining);
+            WITHDRAW_AUTHORIZATIONS.save(deps.storage, key, &authorization)?;
+        }
+    }
+
+    let recipient_addr = authorization.recipient.unwrap_or_else(|| user_addr.clone());
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient_addr.to_string(),
+            amount: coins(withdrawable.u128(), cfg.denom),
+        }))
+        .add_attribute("action", "mars/vesting/withdraw_on_behalf")
+        .add_attribute("user", user_addr)
+        .add_attribute("operator", operator_addr)
+        .add_attribute("recipient", recipient_addr)
+        .add_attribute("timestamp", block.time.seconds().to_string())
+        .add_attribute("withdrawable", withdrawable))
+}
+
+pub fn split_position(
+    deps: DepsMut,
+    info: MessageInfo,
+    amount: Uint128,
+    new_user_addr: Addr,
+) -> Result<Response> {
+    assert_not_tokenized(deps.storage, &info.sender)?;
+
+    let mut position = POSITIONS.load(deps.storage, &info.sender)?;
+
+    if amount.is_zero() || amount >= position.total {
+        return Err(Error::InvalidSplitAmount);
+    }
+
+    if POSITIONS.has(deps.storage, &new_user_addr) {
+        return Err(Error::PositionExists);
+    }
+
+    let new_position = split(&mut position, amount);
+    POSITIONS.save(deps.storage, &info.sender, &position)?;
+    POSITIONS.save(deps.storage, &new_user_addr, &new_position)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/split_position")
+        .add_attribute("user", info.sender)
+        .add_attribute("new_user", new_user_addr)
+        .add_attribute("total", new_position.total)
+        .add_attribute("withdrawn", new_position.withdrawn))
+}
+
+pub fn merge_positions(deps: DepsMut, info: MessageInfo, into_addr: Addr) -> Result<Response> {
+    assert_not_tokenized(deps.storage, &info.sender)?;
+    assert_not_tokenized(deps.storage, &into_addr)?;
+
+    let position = POSITIONS.load(deps.storage, &info.sender)?;
+    let mut into_position = POSITIONS.load(deps.storage, &into_addr)?;
+
+    if info.sender == into_addr
+        || position.vest_schedule != into_position.vest_schedule
+        || position.terminated != into_position.terminated
+    {
+        return Err(Error::ScheduleMismatch);
+    }
+
+    merge(&mut into_position, &position);
+    POSITIONS.save(deps.storage, &into_addr, &into_position)?;
+
+    // the caller no longer has a position, so clean up its settings as well
+    POSITIONS.remove(deps.storage, &info.sender)?;
+    AUTO_DISTRIBUTE.remove(deps.storage, &info.sender);
+    let operators = WITHDRAW_AUTHORIZATIONS
+        .prefix(&info.sender)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for operator_addr in operators {
+        WITHDRAW_AUTHORIZATIONS.remove(deps.storage, (&info.sender, &operator_addr));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/merge_positions")
+        .add_attribute("user", info.sender)
+        .add_attribute("into", into_addr)
+        .add_attribute("total", position.total)
+        .add_attribute("withdrawn", position.withdrawn))
+}
+
+pub fn set_auto_distribute(deps: DepsMut, info: MessageInfo, enabled: bool) -> Result<Response> {
+    if enabled {
+        AUTO_DISTRIBUTE.save(deps.storage, &info.sender, &Empty {})?;
+    } else {
+        AUTO_DISTRIBUTE.remove(deps.storage, &info.sender);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/set_auto_distribute")
+        .add_attribute("user", info.sender)
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+pub fn distribute(
+    deps: DepsMut,
+    block: &BlockInfo,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> Result<Response> {
     let withdraw_enabled = WITHDRAW_ENABLED.may_load(deps.storage)?.unwrap_or(true);
     if !withdraw_enabled {
         return Err(Error::WithdrawDisabled);
     }
 
     let cfg = CONFIG.load(deps.storage)?;
-    let mut position = POSITIONS.load(deps.storage, &user_addr)?;
 
-    let (_, _, withdrawable) = compute_withdrawable(
-        time,
+    // an empty batch would reset the sweep without distributing anything
+    if limit == Some(0) {
+        return Err(Error::ZeroLimit);
+    }
+
+    // resume from where the previous batch left off, unless told otherwise; batches starting
+    // elsewhere leave the sweep where it is
+    let resume = start_after.is_none();
+    let start_after = match start_after {
+        Some(addr) => Some(addr),
+        None => DISTRIBUTE_CURSOR.may_load(deps.storage)?,
+    };
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let limit = pagination::limit(limit);
+
+    let positions = POSITIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // if fewer positions than the limit were found, the sweep has reached the end, and the next
+    // one starts over from the beginning
+    if resume {
+        match positions.last() {
+            Some((user_addr, _)) if positions.len() == limit => {
+                DISTRIBUTE_CURSOR.save(deps.storage, user_addr)?;
+            }
+            _ => DISTRIBUTE_CURSOR.remove(deps.storage),
+        }
+    }
+
+    let mut msgs = vec![];
+    for (user_addr, mut position) in positions {
+        // tokenized positions are paid out to the NFT owner, who has to withdraw themselves
+        if !AUTO_DISTRIBUTE.has(deps.storage, &user_addr)
+            || TOKENIZED_POSITIONS.has(deps.storage, &user_addr)
+        {
+            continue;
+        }
+
+        let (_, _, withdrawable) = compute_withdrawable(
+            block,
+            position.total,
+            position.withdrawn,
+            position.held_back,
+            &position.vest_schedule,
+            &cfg.unlock_schedule,
+        );
+
+        if withdrawable.is_zero() {
+            continue;
+        }
+
+        position.withdrawn += withdrawable;
+        POSITIONS.save(deps.storage, &user_addr, &position)?;
+
+        msgs.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: user_addr.into(),
+            amount: coins(withdrawable.u128(), &cfg.denom),
+        }));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/distribute")
+        .add_attribute("timestamp", block.time.seconds().to_string())
+        .add_attribute("distributions", msgs.len().to_string())
+        .add_messages(msgs))
+}
+
+/// Positions represented by NFTs can only be withdrawn from by the NFT owner, using
+/// `WithdrawToken`, and can't be split or merged.
+fn assert_not_tokenized(store: &dyn Storage, user_addr: &Addr) -> Result<()> {
+    if TOKENIZED_POSITIONS.has(store, user_addr) {
+        return Err(Error::PositionTokenized);
+    }
+
+    Ok(())
+}
+
+/// Ensure the sender currently owns the NFT representing a position. Returns the address of the
+/// user the position was created for, which the token ID is.
+fn assert_token_owner(deps: Deps, sender_addr: &Addr, token_id: String) -> Result<Addr> {
+    let user_addr = deps.api.addr_validate(&token_id)?;
+
+    let Some(nft_contract) = TOKENIZED_POSITIONS.may_load(deps.storage, &user_addr)? else {
+        return Err(Error::PositionNotTokenized);
+    };
+
+    let owner_res: OwnerOfResponse = deps.querier.query_wasm_smart(
+        nft_contract,
+        &Cw721QueryMsg::OwnerOf {
+            token_id,
+            include_expired: None,
+        },
+    )?;
+
+    if *sender_addr != owner_res.owner {
+        return Err(Error::NotTokenOwner);
+    }
+
+    Ok(user_addr)
+}
+
+/// Record a withdrawal of the position's withdrawable amount, capped at `max_amount` if provided.
+/// Returns the amount withdrawn.
+fn withdraw_position(
+    store: &mut dyn Storage,
+    block: &BlockInfo,
+    cfg: &Config<Addr>,
+    user_addr: &Addr,
+    max_amount: Option<Uint128>,
+) -> Result<Uint128> {
+    let withdraw_enabled = WITHDRAW_ENABLED.may_load(store)?.unwrap_or(true);
+    if !withdraw_enabled {
+        return Err(Error::WithdrawDisabled);
+    }
+
+    let mut position = POSITIONS.load(store, user_addr)?;
+
+    let (_, _, mut withdrawable) = compute_withdrawable(
+        block,
         position.total,
         position.withdrawn,
+        position.held_back,
         &position.vest_schedule,
         &cfg.unlock_schedule,
     );
 
+    if let Some(max_amount) = max_amount {
+        withdrawable = withdrawable.min(max_amount);
+    }
+
     if withdrawable.is_zero() {
         return Err(Error::ZeroWithdrawable);
     }
 
     position.withdrawn += withdrawable;
-    POSITIONS.save(deps.storage, &user_addr, &position)?;
+    POSITIONS.save(store, user_addr, &position)?;
+
+    Ok(withdrawable)
+}
+
+pub fn grant_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    user_addr: Addr,
+    role: Role,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only admins can grant roles
+    assert_role(deps.storage, &cfg, &info.sender, Role::Admin)?;
+
+    ROLES.update(deps.storage, &user_addr, |roles| -> Result<_> {
+        let mut roles = roles.unwrap_or_default();
+        if !roles.contains(&role) {
+            roles.push(role);
+        }
+        Ok(roles)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/grant_role")
+        .add_attribute("user", user_addr)
+        .add_attribute("role", role.to_string()))
+}
+
+pub fn revoke_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    user_addr: Addr,
+    role: Role,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only admins can revoke roles
+    assert_role(deps.storage, &cfg, &info.sender, Role::Admin)?;
+
+    let mut roles = ROLES.may_load(deps.storage, &user_addr)?.unwrap_or_default();
+    roles.retain(|r| *r != role);
+
+    if roles.is_empty() {
+        ROLES.remove(deps.storage, &user_addr);
+    } else {
+        ROLES.save(deps.storage, &user_addr, &roles)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/revoke_role")
+        .add_attribute("user", user_addr)
+        .add_attribute("role", role.to_string()))
+}
+
+pub fn set_withdraw_enabled(deps: DepsMut, info: MessageInfo, enabled: bool) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only pausers can enable or disable withdrawals
+    assert_role(deps.storage, &cfg, &info.sender, Role::Pauser)?;
+
+    WITHDRAW_ENABLED.save(deps.storage, &enabled)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/set_withdraw_enabled")
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+pub fn sweep_surplus(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient_addr: Addr,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only admins can sweep surplus funds
+    assert_role(deps.storage, &cfg, &info.sender, Role::Admin)?;
+
+    let solvency = query_solvency(deps.as_ref(), env)?;
+
+    if solvency.surplus.is_zero() {
+        return Err(Error::NoSurplus);
+    }
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient_addr.to_string(),
+            amount: coins(solvency.surplus.u128(), cfg.denom),
+        }))
+        .add_attribute("action", "mars/vesting/sweep_surplus")
+        .add_attribute("recipient", recipient_addr)
+        .add_attribute("amount", solvency.surplus))
+}
+
+pub fn emergency_shutdown(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recovery_addr: Addr,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only admins can trigger an emergency shutdown
+    assert_role(deps.storage, &cfg, &info.sender, Role::Admin)?;
+
+    // positions are terminated as of this block later on, by `EmergencyRecover` or when withdrawn
+    // from, as there may be too many of them to terminate in a single transaction
+    EMERGENCY.save(
+        deps.storage,
+        &EmergencyState {
+            triggered_at: env.block.time.seconds(),
+            triggered_height: env.block.height,
+            recovery_addr: recovery_addr.to_string(),
+            recovered: Uint128::zero(),
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/emergency_shutdown")
+        .add_attribute("recovery_addr", recovery_addr)
+        .add_attribute("timestamp", env.block.time.seconds().to_string()))
+}
+
+pub fn emergency_recover(deps: DepsMut, block: &BlockInfo, limit: Option<u32>) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut emergency = EMERGENCY.may_load(deps.storage)?.ok_or(Error::NotShutdown)?;
+
+    // an empty batch would reset the sweep without terminating anything
+    if limit == Some(0) {
+        return Err(Error::ZeroLimit);
+    }
+
+    let start_after = EMERGENCY_CURSOR.may_load(deps.storage)?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let limit = pagination::limit(limit);
+
+    let positions = POSITIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    // if fewer positions than the limit were found, the sweep has reached the end; positions
+    // already terminated are skipped, so sweeping again is harmless
+    match positions.last() {
+        Some((user_addr, _)) if positions.len() == limit => {
+            EMERGENCY_CURSOR.save(deps.storage, user_addr)?;
+        }
+        _ => EMERGENCY_CURSOR.remove(deps.storage),
+    }
+
+    let mut recovered = Uint128::zero();
+    for (user_addr, mut position) in positions {
+        if position.terminated {
+            continue;
+        }
+
+        recovered += recover_position(&mut emergency, block, &mut position, &cfg.unlock_schedule);
+        POSITIONS.save(deps.storage, &user_addr, &position)?;
+    }
+
+    EMERGENCY.save(deps.storage, &emergency)?;
+
+    Ok(Response::new()
+        .add_messages(recovery_msgs(&emergency, &cfg.denom, recovered))
+        .add_attribute("action", "mars/vesting/emergency_recover")
+        .add_attribute("recovery_addr", emergency.recovery_addr)
+        .add_attribute("recovered", recovered))
+}
+
+pub fn emergency_withdraw(deps: DepsMut, block: &BlockInfo, user_addr: Addr) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    assert_not_tokenized(deps.storage, &user_addr)?;
+
+    let (withdrawable, recovery_msgs) =
+        emergency_withdraw_position(deps.storage, block, &cfg, &user_addr)?;
 
     Ok(Response::new()
         .add_message(CosmosMsg::Bank(BankMsg::Send {
             to_address: user_addr.to_string(),
             amount: coins(withdrawable.u128(), cfg.denom),
         }))
-        .add_attribute("action", "mars/vesting/withdraw")
+        .add_messages(recovery_msgs)
+        .add_attribute("action", "mars/vesting/emergency_withdraw")
+        .add_attribute("user", user_addr)
+        .add_attribute("timestamp", block.time.seconds().to_string())
+        .add_attribute("withdrawable", withdrawable))
+}
+
+pub fn emergency_withdraw_token(
+    deps: DepsMut,
+    block: &BlockInfo,
+    sender_addr: Addr,
+    token_id: String,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let user_addr = assert_token_owner(deps.as_ref(), &sender_addr, token_id)?;
+
+    let (withdrawable, recovery_msgs) =
+        emergency_withdraw_position(deps.storage, block, &cfg, &user_addr)?;
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: sender_addr.to_string(),
+            amount: coins(withdrawable.u128(), cfg.denom),
+        }))
+        .add_messages(recovery_msgs)
+        .add_attribute("action", "mars/vesting/emergency_withdraw_token")
         .add_attribute("user", user_addr)
-        .add_attribute("timestamp", time.to_string())
+        .add_attribute("owner", sender_addr)
+        .add_attribute("timestamp", block.time.seconds().to_string())
         .add_attribute("withdrawable", withdrawable))
 }
 
+/// Record a withdrawal following an emergency shutdown, according to the config's
+/// `emergency_release`, terminating the position first if `EmergencyRecover` hasn't yet. Returns
+/// the amount withdrawn, and the messages sending the position's unvested tokens to the recovery
+/// address, if any.
+fn emergency_withdraw_position(
+    store: &mut dyn Storage,
+    block: &BlockInfo,
+    cfg: &Config<Addr>,
+    user_addr: &Addr,
+) -> Result<(Uint128, Vec<CosmosMsg>)> {
+    let mut emergency = EMERGENCY.may_load(store)?.ok_or(Error::NotShutdown)?;
+
+    let mut position = POSITIONS.load(store, user_addr)?;
+
+    let mut recovered = Uint128::zero();
+    if !position.terminated {
+        recovered = recover_position(&mut emergency, block, &mut position, &cfg.unlock_schedule);
+        EMERGENCY.save(store, &emergency)?;
+    }
+
+    // the position has been terminated, so its total is the vested amount
+    let withdrawable = match cfg.emergency_release {
+        EmergencyRelease::Vested => position.total - position.withdrawn,
+        EmergencyRelease::VestedAndUnlocked => {
+            let (_, _, withdrawable) = compute_withdrawable(
+                block,
+                position.total,
+                position.withdrawn,
+                position.held_back,
+                &position.vest_schedule,
+                &cfg.unlock_schedule,
+            );
+            withdrawable
+        }
+    };
+
+    if withdrawable.is_zero() {
+        return Err(Error::ZeroWithdrawable);
+    }
+
+    position.withdrawn += withdrawable;
+    POSITIONS.save(store, user_addr, &position)?;
+
+    Ok((withdrawable, recovery_msgs(&emergency, &cfg.denom, recovered)))
+}
+
+/// Terminate a position as of the block in which the emergency shutdown was triggered, adding its
+/// unvested tokens to those recovered. Returns the amount of unvested tokens.
+fn recover_position(
+    emergency: &mut EmergencyState,
+    block: &BlockInfo,
+    position: &mut Position,
+    unlock_schedule: &Schedule,
+) -> Uint128 {
+    let triggered_block = BlockInfo {
+        height: emergency.triggered_height,
+        time: Timestamp::from_seconds(emergency.triggered_at),
+        chain_id: block.chain_id.clone(),
+    };
+
+    let recovered = terminate(&triggered_block, position, unlock_schedule);
+    emergency.recovered += recovered;
+
+    recovered
+}
+
+/// Send unvested tokens to the recovery address; there is nothing to send if no position had
+/// unvested tokens
+fn recovery_msgs(emergency: &EmergencyState, denom: &str, amount: Uint128) -> Vec<CosmosMsg> {
+    if amount.is_zero() {
+        return vec![];
+    }
+
+    vec![CosmosMsg::Bank(BankMsg::Send {
+        to_address: emergency.recovery_addr.clone(),
+        amount: coins(amount.u128(), denom),
+    })]
+}
+
 //--------------------------------------------------------------------------------------------------
 // Queries
 //--------------------------------------------------------------------------------------------------
@@ -206,24 +976,86 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary> {
     let api = deps.api;
     match msg {
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
+        QueryMsg::PendingConfig {} => to_json_binary(&query_pending_config(deps)?),
         QueryMsg::VotingPower {
             user,
         } => to_json_binary(&query_voting_power(deps, api.addr_validate(&user)?)?),
         QueryMsg::VotingPowers {
             start_after,
+            start_before,
             limit,
-        } => to_json_binary(&query_voting_powers(deps, start_after, limit)?),
+            order,
+        } => to_json_binary(&query_voting_powers(deps, start_after, start_before, limit, order)?),
         QueryMsg::Position {
             user,
-        } => to_json_binary(&query_position(
+        } => to_json_binary(&query_position(deps, &env.block, api.addr_validate(&user)?)?),
+        QueryMsg::Positions {
+            start_after,
+            start_before,
+            limit,
+            order,
+        } => to_json_binary(&query_positions(
+            deps,
+            &env.block,
+            start_after,
+            start_before,
+            limit,
+            order,
+        )?),
+        QueryMsg::PositionsBy {
+            filter,
+            start_after,
+            start_before,
+            limit,
+            order,
+        } => to_json_binary(&query_positions_by(
+            deps,
+            &env.block,
+            filter,
+            start_after,
+            start_before,
+            limit,
+            order,
+        )?),
+        QueryMsg::EmergencyState {} => to_json_binary(&query_emergency_state(deps)?),
+        QueryMsg::Solvency {} => to_json_binary(&query_solvency(deps, env)?),
+        QueryMsg::WithdrawAuthorizations {
+            user,
+            start_after,
+            start_before,
+            limit,
+            order,
+        } => to_json_binary(&query_withdraw_authorizations(
             deps,
             env.block.time.seconds(),
             api.addr_validate(&user)?,
+            start_after,
+            start_before,
+            limit,
+            order,
         )?),
-        QueryMsg::Positions {
+        QueryMsg::Stream {
+            id,
+        } => to_json_binary(&stream::query_stream(deps, env.block.time.seconds(), id)?),
+        QueryMsg::Streams {
             start_after,
+            start_before,
             limit,
-        } => to_json_binary(&query_positions(deps, env.block.time.seconds(), start_after, limit)?),
+            order,
+        } => to_json_binary(&stream::query_streams(
+            deps,
+            env.block.time.seconds(),
+            start_after,
+            start_before,
+            limit,
+            order,
+        )?),
+        QueryMsg::Roles {
+            start_after,
+            start_before,
+            limit,
+            order,
+        } => to_json_binary(&query_roles(deps, start_after, start_before, limit, order)?),
     }
     .map_err(Into::into)
 }
@@ -233,6 +1065,11 @@ pub fn query_config(deps: Deps) -> Result<Config<String>> {
     Ok(cfg.into())
 }
 
+pub fn query_pending_config(deps: Deps) -> Result<Option<PendingConfig<String>>> {
+    let pending = PENDING_CONFIG.may_load(deps.storage)?;
+    Ok(pending.map(Into::into))
+}
+
 pub fn query_voting_power(deps: Deps, user_addr: Addr) -> Result<VotingPowerResponse> {
     let voting_power = match POSITIONS.may_load(deps.storage, &user_addr) {
         Ok(Some(position)) => position.total - position.withdrawn,
@@ -246,69 +1083,230 @@ pub fn query_voting_power(deps: Deps, user_addr: Addr) -> Result<VotingPowerResp
     })
 }
 
-pub fn query_position(deps: Deps, time: u64, user_addr: Addr) -> Result<PositionResponse> {
+pub fn query_position(deps: Deps, block: &BlockInfo, user_addr: Addr) -> Result<PositionResponse> {
     let cfg = CONFIG.load(deps.storage)?;
     let position = POSITIONS.load(deps.storage, &user_addr)?;
 
-    Ok(compute_position_response(time, user_addr, &position, &cfg.unlock_schedule))
+    Ok(compute_position_response(block, user_addr, &position, &cfg.unlock_schedule))
 }
 
 pub fn query_voting_powers(
     deps: Deps,
     start_after: Option<String>,
+    start_before: Option<String>,
     limit: Option<u32>,
-) -> Result<Vec<VotingPowerResponse>> {
-    let addr: Addr;
-    let start = match &start_after {
-        Some(addr_str) => {
-            addr = deps.api.addr_validate(addr_str)?;
-            Some(Bound::exclusive(&addr))
-        }
-        None => None,
-    };
+    order: Option<OrderBy>,
+) -> Result<Paginated<VotingPowerResponse>> {
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start_before = start_before.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let (min, max) = pagination::bounds(start_after.as_ref(), start_before.as_ref());
 
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let total = POSITIONS.keys_raw(deps.storage, None, None, Order::Ascending).count();
 
-    POSITIONS
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
-        .map(|res| {
+    let voting_powers =
+        POSITIONS.range(deps.storage, min, max, order.unwrap_or_default().into()).map(|res| {
             let (user_addr, position) = res?;
             Ok(VotingPowerResponse {
                 user: user_addr.to_string(),
                 voting_power: position.total - position.withdrawn,
             })
-        })
-        .collect()
+        });
+
+    pagination::paginate(voting_powers, pagination::limit(limit), total as u64, |res| {
+        res.user.clone()
+    })
+    .map_err(Into::into)
 }
 
 pub fn query_positions(
     deps: Deps,
-    time: u64,
+    block: &BlockInfo,
     start_after: Option<String>,
+    start_before: Option<String>,
     limit: Option<u32>,
-) -> Result<Vec<PositionResponse>> {
+    order: Option<OrderBy>,
+) -> Result<Paginated<PositionResponse>> {
     let cfg = CONFIG.load(deps.storage)?;
 
-    let addr: Addr;
-    let start = match &start_after {
-        Some(addr_str) => {
-            addr = deps.api.addr_validate(addr_str)?;
-            Some(Bound::exclusive(&addr))
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start_before = start_before.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let (min, max) = pagination::bounds(start_after.as_ref(), start_before.as_ref());
+
+    let total = POSITIONS.keys_raw(deps.storage, None, None, Order::Ascending).count();
+
+    let positions =
+        POSITIONS.range(deps.storage, min, max, order.unwrap_or_default().into()).map(|res| {
+            let (user_addr, position) = res?;
+            Ok(compute_position_response(block, user_addr, &position, &cfg.unlock_schedule))
+        });
+
+    pagination::paginate(positions, pagination::limit(limit), total as u64, |res| res.user.clone())
+        .map_err(Into::into)
+}
+
+pub fn query_positions_by(
+    deps: Deps,
+    block: &BlockInfo,
+    filter: PositionFilter,
+    start_after: Option<String>,
+    start_before: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> Result<Paginated<PositionResponse>> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start_before = start_before.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let order = order.unwrap_or_default().into();
+    let limit = pagination::limit(limit);
+
+    let to_response = |res: StdResult<(Addr, Position)>| {
+        let (user_addr, position) = res?;
+        Ok(compute_position_response(block, user_addr, &position, &cfg.unlock_schedule))
+    };
+    let cursor = |res: &PositionResponse| res.user.clone();
+
+    let page = match filter {
+        PositionFilter::StartTime {
+            min,
+            max,
+        } => {
+            // the lowest possible key for a given start time is that time paired with an empty
+            // address
+            let lower = min.map(|min| Bound::inclusive((min, Addr::unchecked(""))));
+            let upper = max
+                .and_then(|max| max.checked_add(1))
+                .map(|max| Bound::exclusive((max, Addr::unchecked(""))));
+
+            let idx = &POSITIONS.idx.start_time;
+            let total = idx.keys_raw(deps.storage, lower.clone(), upper.clone(), order).count();
+
+            // the cursors are positions, which are indexed by their start time and address
+            let cursor_bound = |addr: Option<Addr>| -> StdResult<_> {
+                addr.map(|addr| {
+                    let position = POSITIONS.load(deps.storage, &addr)?;
+                    Ok(Bound::exclusive((position.vest_schedule.start_time, addr)))
+                })
+                .transpose()
+            };
+            let lower = cursor_bound(start_after)?.or(lower);
+            let upper = cursor_bound(start_before)?.or(upper);
+
+            let positions = idx.range(deps.storage, lower, upper, order).map(to_response);
+            pagination::paginate(positions, limit, total as u64, cursor)?
+        }
+        PositionFilter::Status {
+            status,
+        } => {
+            let key = match status {
+                PositionStatus::Active | PositionStatus::FullyVested => STATUS_VESTING,
+                PositionStatus::Terminated => STATUS_TERMINATED,
+                PositionStatus::FullyWithdrawn => STATUS_FULLY_WITHDRAWN,
+            };
+
+            // active and fully vested positions share a key, so tell them apart by the current
+            // time; errors are kept so that they surface when paginating
+            let matches_status = |res: &StdResult<(Addr, Position)>| match res {
+                Ok((_, position)) => compute_status(block, position) == status,
+                Err(_) => true,
+            };
+
+            let prefix = POSITIONS.idx.status.prefix(key);
+            let total =
+                prefix.range(deps.storage, None, None, order).filter(matches_status).count();
+
+            let (min, max) = pagination::bounds(start_after, start_before);
+            let positions =
+                prefix.range(deps.storage, min, max, order).filter(matches_status).map(to_response);
+            pagination::paginate(positions, limit, total as u64, cursor)?
         }
-        None => None,
     };
 
-    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    Ok(page)
+}
 
-    POSITIONS
-        .range(deps.storage, start, None, Order::Ascending)
-        .take(limit)
+pub fn query_emergency_state(deps: Deps) -> Result<Option<EmergencyState>> {
+    EMERGENCY.may_load(deps.storage).map_err(Into::into)
+}
+
+pub fn query_solvency(deps: Deps, env: Env) -> Result<SolvencyResponse> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let balance = deps.querier.query_balance(env.contract.address, cfg.denom)?.amount;
+    let liabilities = compute_liabilities(deps.storage)?;
+
+    Ok(SolvencyResponse {
+        balance,
+        liabilities,
+        surplus: balance.saturating_sub(liabilities),
+        deficit: liabilities.saturating_sub(balance),
+    })
+}
+
+pub fn query_withdraw_authorizations(
+    deps: Deps,
+    time: u64,
+    user_addr: Addr,
+    start_after: Option<String>,
+    start_before: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> Result<Paginated<WithdrawAuthorizationResponse>> {
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start_before = start_before.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let (min, max) = pagination::bounds(start_after.as_ref(), start_before.as_ref());
+
+    let not_expired = |res: &StdResult<(Addr, WithdrawAuthorization)>| match res {
+        Ok((_, authorization)) => !authorization.is_expired(time),
+        Err(_) => true,
+    };
+
+    let prefix = WITHDRAW_AUTHORIZATIONS.prefix(&user_addr);
+    let total =
+        prefix.range(deps.storage, None, None, Order::Ascending).filter(not_expired).count();
+
+    let authorizations = prefix
+        .range(deps.storage, min, max, order.unwrap_or_default().into())
+        .filter(not_expired)
         .map(|res| {
-            let (user_addr, position) = res?;
-            Ok(compute_position_response(time, user_addr, &position, &cfg.unlock_schedule))
+            let (operator_addr, authorization) = res?;
+            Ok(WithdrawAuthorizationResponse {
+                operator: operator_addr.to_string(),
+                expiry: authorization.expiry,
+                max_amount: authorization.max_amount,
+                recipient: authorization.recipient.map(Into::into),
+            })
+        });
+
+    pagination::paginate(authorizations, pagination::limit(limit), total as u64, |res| {
+        res.operator.clone()
+    })
+    .map_err(Into::into)
+}
+
+pub fn query_roles(
+    deps: Deps,
+    start_after: Option<String>,
+    start_before: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> Result<Paginated<RolesResponse>> {
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let start_before = start_before.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let (min, max) = pagination::bounds(start_after.as_ref(), start_before.as_ref());
+
+    let total = ROLES.keys_raw(deps.storage, None, None, Order::Ascending).count();
+
+    let roles = ROLES.range(deps.storage, min, max, order.unwrap_or_default().into()).map(|res| {
+        let (user_addr, roles) = res?;
+        Ok(RolesResponse {
+            user: user_addr.to_string(),
+            roles,
         })
-        .collect()
+    });
+
+    pagination::paginate(roles, pagination::limit(limit), total as u64, |res| res.user.clone())
+        .map_err(Into::into)
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -321,5 +1319,6 @@ pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response> {
         MigrateMsg::V1_0_0ToV1_1_0 {} => v1_1_0::migrate(deps),
         MigrateMsg::V1_1_0ToV1_1_1(updates) => v1_1_1::migrate(deps, updates),
         MigrateMsg::V1_1_1ToV1_1_2(updates) => v1_1_2::migrate(deps, env, updates),
+        MigrateMsg::V1_1_2ToV1_2_0 {} => v1_2_0::migrate(deps),
     }
 }
@@ -0,0 +1,35 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Empty;
+use cw_utils::Expiration;
+
+/// The subset of CW721 execute messages used by this contract
+#[cw_serde]
+pub enum Cw721ExecuteMsg {
+    Mint {
+        token_id: String,
+        owner: String,
+        token_uri: Option<String>,
+        extension: Option<Empty>,
+    },
+}
+
+/// The subset of CW721 queries used by this contract
+#[cw_serde]
+pub enum Cw721QueryMsg {
+    OwnerOf {
+        token_id: String,
+        include_expired: Option<bool>,
+    },
+}
+
+#[cw_serde]
+pub struct Approval {
+    pub spender: String,
+    pub expires: Expiration,
+}
+
+#[cw_serde]
+pub struct OwnerOfResponse {
+    pub owner: String,
+    pub approvals: Vec<Approval>,
+}
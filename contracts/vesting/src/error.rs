@@ -1,3 +1,5 @@
+use crate::msg::Role;
+
 #[derive(Debug, PartialEq, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
@@ -6,18 +8,81 @@ pub enum Error {
     #[error(transparent)]
     Payment(#[from] cw_utils::PaymentError),
 
-    #[error("caller is not owner")]
-    NotOwner,
+    #[error("caller does not have the {0} role")]
+    MissingRole(Role),
 
     #[error("a vesting position already exists for this user")]
     PositionExists,
 
+    #[error("split amount must be greater than zero and less than the position's total")]
+    InvalidSplitAmount,
+
+    #[error("positions to be merged must have identical vesting schedules")]
+    ScheduleMismatch,
+
+    #[error("invalid schedule: {reason}")]
+    InvalidSchedule {
+        reason: String,
+    },
+
     #[error("withdrawable amount is zero")]
     ZeroWithdrawable,
 
     #[error("withdraw is disabled")]
     WithdrawDisabled,
 
+    #[error("position is represented by an NFT and can only be withdrawn from by its owner")]
+    PositionTokenized,
+
+    #[error("position is not represented by an NFT")]
+    PositionNotTokenized,
+
+    #[error("caller is not the owner of the NFT")]
+    NotTokenOwner,
+
+    #[error("operator is not authorized to withdraw on behalf of this user")]
+    NotAuthorized,
+
+    #[error("batch limit must be greater than zero")]
+    ZeroLimit,
+
+    #[error("stream rate must be greater than zero")]
+    InvalidStreamRate,
+
+    #[error("caller is not the payer of this stream")]
+    NotStreamPayer,
+
+    #[error("caller is not the recipient of this stream")]
+    NotStreamRecipient,
+
+    #[error(
+        "cannot change denom from {current} to {new} while vesting positions or streams exist"
+    )]
+    DenomLocked {
+        current: String,
+        new: String,
+    },
+
+    #[error("contract holds no tokens in excess of its liabilities")]
+    NoSurplus,
+
+    #[error("contract has been shut down")]
+    Shutdown,
+
+    #[error("contract has not been shut down")]
+    NotShutdown,
+
+    #[error("there is no pending config change")]
+    NoPendingConfig,
+
+    #[error(
+        "timelock has not elapsed yet! executable at: {executable_at}, current: {current_time}"
+    )]
+    TimelockNotElapsed {
+        executable_at: u64,
+        current_time: u64,
+    },
+
     #[error("{0}")]
     Version(#[from] cw2::VersionError),
 
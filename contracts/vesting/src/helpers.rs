@@ -1,29 +1,144 @@
 use std::cmp::min;
 
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Addr, BlockInfo, Decimal, Order, StdResult, Storage, Uint128};
 
-use crate::msg::{Position, PositionResponse, Schedule};
+use crate::{
+    error::{Error, Result},
+    msg::{Config, Curve, Position, PositionResponse, PositionStatus, Role, Schedule},
+    state::{status_key, POSITIONS, ROLES, STATUS_FULLY_WITHDRAWN, STATUS_TERMINATED, STREAMS},
+};
+
+/// Return an error if the address does not hold the given role.
+///
+/// The contract owner and admins implicitly hold every role.
+pub fn assert_role(store: &dyn Storage, cfg: &Config<Addr>, addr: &Addr, role: Role) -> Result<()> {
+    if *addr == cfg.owner {
+        return Ok(());
+    }
+
+    let roles = ROLES.may_load(store, addr)?.unwrap_or_default();
+    if roles.contains(&Role::Admin) || roles.contains(&role) {
+        return Ok(());
+    }
+
+    Err(Error::MissingRole(role))
+}
+
+/// Maximum number of points a piecewise-linear curve can have
+pub const MAX_CURVE_POINTS: usize = 10;
+
+/// Return an error if the schedule's cliff or linear start is out of range, or if its curve is not
+/// monotonically increasing from 0 to 1.
+pub fn assert_valid_schedule(schedule: &Schedule) -> Result<()> {
+    let invalid = |reason: &str| Error::InvalidSchedule {
+        reason: reason.to_string(),
+    };
+
+    if schedule.cliff_percent.is_some_and(|percent| percent > Decimal::one()) {
+        return Err(invalid("cliff percent must not exceed 1"));
+    }
+
+    if let Some(linear_start) = schedule.linear_start {
+        if linear_start < schedule.start_time
+            || linear_start >= schedule.start_time + schedule.duration
+        {
+            return Err(invalid("linear start must be within the schedule's duration"));
+        }
+    }
+
+    match &schedule.curve {
+        Curve::Linear => {}
+        Curve::Power {
+            exponent,
+        }
+        | Curve::InversePower {
+            exponent,
+        } => {
+            if *exponent == 0 {
+                return Err(invalid("exponent must be greater than zero"));
+            }
+        }
+        Curve::PiecewiseLinear {
+            points,
+        } => {
+            if points.is_empty() || points.len() > MAX_CURVE_POINTS {
+                return Err(invalid(&format!(
+                    "number of points must be between 1 and {MAX_CURVE_POINTS}"
+                )));
+            }
+
+            let mut prev_time = Decimal::zero();
+            let mut prev_amount = Decimal::zero();
+            for point in points {
+                if point.time <= prev_time || point.time >= Decimal::one() {
+                    return Err(invalid("point times must be strictly increasing between 0 and 1"));
+                }
+                if point.amount < prev_amount || point.amount > Decimal::one() {
+                    return Err(invalid("point amounts must be non-decreasing between 0 and 1"));
+                }
+                prev_time = point.time;
+                prev_amount = point.amount;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Return an error if the new config changes the denom while there are positions funded in the
+/// current one, which would leave the contract unable to pay them out. The same applies to streams.
+pub fn assert_denom_unchanged(
+    store: &dyn Storage,
+    cfg: &Config<Addr>,
+    new_cfg: &Config<Addr>,
+) -> Result<()> {
+    if new_cfg.denom != cfg.denom && !(POSITIONS.is_empty(store) && STREAMS.is_empty(store)) {
+        return Err(Error::DenomLocked {
+            current: cfg.denom.clone(),
+            new: new_cfg.denom.clone(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Total amount of tokens the contract owes to vesting and stream recipients, i.e. the sum of all
+/// positions' unwithdrawn amounts plus all streams' unstreamed and unwithdrawn amounts
+pub fn compute_liabilities(store: &dyn Storage) -> StdResult<Uint128> {
+    let positions = POSITIONS.range(store, None, None, Order::Ascending).try_fold(
+        Uint128::zero(),
+        |acc, res| -> StdResult<_> {
+            let (_, position) = res?;
+            Ok(acc + position.total - position.withdrawn)
+        },
+    )?;
+
+    STREAMS.range(store, None, None, Order::Ascending).try_fold(positions, |acc, res| {
+        let (_, stream) = res?;
+        Ok(acc + stream.remaining + stream.accrued)
+    })
+}
 
 /// The return value is a three-tuple consists of: the vested amount, the unlocked amount, and the
 /// withdrawable amount
+///
+/// Each schedule is evaluated against the block time or height, depending on its time unit.
+/// Until the total is released, `held_back` is deducted from the amounts released.
 pub fn compute_withdrawable(
-    time: u64,
+    block: &BlockInfo,
     total: Uint128,
     withdrawn: Uint128,
+    held_back: Uint128,
     vest_schedule: &Schedule,
     unlock_schedule: &Schedule,
 ) -> (Uint128, Uint128, Uint128) {
     let compute = |schedule: &Schedule| {
-        // before the end of cliff period, no token will be vested/unlocked
-        if time < schedule.start_time + schedule.cliff {
-            return Uint128::zero();
-        }
-        // after the duration, all tokens are fully vested/unlocked
-        if time > schedule.start_time + schedule.duration {
-            return total;
+        let released = compute_released(schedule, total, schedule.unit.current(block));
+        if released < total {
+            released.saturating_sub(held_back)
+        } else {
+            released
         }
-        // otherwise, tokens vest/unlock linearly
-        total.multiply_ratio(time - schedule.start_time, schedule.duration)
     };
 
     let vested = compute(vest_schedule);
@@ -35,27 +150,160 @@ pub fn compute_withdrawable(
     (vested, unlocked, withdrawable)
 }
 
+/// Amount of the total vested/unlocked by the given time, according to the schedule
+fn compute_released(schedule: &Schedule, total: Uint128, time: u64) -> Uint128 {
+    let end = schedule.start_time + schedule.duration;
+    // before the end of cliff period, no token will be vested/unlocked
+    if time < schedule.start_time + schedule.cliff {
+        return Uint128::zero();
+    }
+    // after the duration, all tokens are fully vested/unlocked
+    if time >= end {
+        return total;
+    }
+
+    // the lump sum released at the cliff, if specified; the rest is released along the curve
+    let cliff_amount =
+        schedule.cliff_percent.map(|percent| total.mul_floor(percent)).unwrap_or_default();
+    let remaining = total - cliff_amount;
+
+    let linear_start = schedule.linear_start.unwrap_or(schedule.start_time);
+    if time <= linear_start {
+        return cliff_amount;
+    }
+
+    let elapsed = time - linear_start;
+    let duration = end - linear_start;
+    cliff_amount
+        + match &schedule.curve {
+            Curve::Linear => remaining.multiply_ratio(elapsed, duration),
+            curve => remaining.mul_floor(curve.fraction(Decimal::from_ratio(elapsed, duration))),
+        }
+}
+
+/// Stop the vesting of a position at the current block: its total becomes the amount vested so far,
+/// and its vesting ends now. Returns the unvested amount, which is to be reclaimed.
+///
+/// The total never drops below the amount already withdrawn, which may exceed the amount vested for
+/// a position split off from another one.
+pub fn terminate(
+    block: &BlockInfo,
+    position: &mut Position,
+    unlock_schedule: &Schedule,
+) -> Uint128 {
+    let (vested, _, _) = compute_withdrawable(
+        block,
+        position.total,
+        position.withdrawn,
+        position.held_back,
+        &position.vest_schedule,
+        unlock_schedule,
+    );
+
+    let vested = vested.max(position.withdrawn);
+    let reclaim = position.total - vested;
+
+    position.total = vested;
+    position.held_back = Uint128::zero();
+    position.terminated = true;
+    let time = position.vest_schedule.unit.current(block);
+    position.vest_schedule.duration = time.saturating_sub(position.vest_schedule.start_time);
+
+    reclaim
+}
+
+/// Upper bound on the amount by which the two parts of a split position can release more than the
+/// original one at any time, as each of them rounds the amounts released down separately
+const SPLIT_ROUNDING: Uint128 = Uint128::new(1);
+
+/// Upper bound on the amount by which a merged position can release more than the two positions
+/// it's made of at any time, as it rounds the amounts released down once rather than twice
+const MERGE_ROUNDING: Uint128 = Uint128::new(3);
+
+/// Divide a position in two, moving `amount` of its total to a new position with the same vesting
+/// schedule. The withdrawn amount is divided in proportion to the totals, with the amount moved
+/// rounded up, so that the sum of the two positions' totals and withdrawn amounts equal those of
+/// the original position exactly.
+///
+/// The new position holds back more than the original, so that the two never release more than the
+/// original would have.
+///
+/// The caller must ensure that `amount` is non-zero and less than the position's total.
+pub fn split(position: &mut Position, amount: Uint128) -> Position {
+    let mut withdrawn_moved = position.withdrawn.multiply_ratio(amount, position.total);
+    if withdrawn_moved.full_mul(position.total) < position.withdrawn.full_mul(amount) {
+        withdrawn_moved += Uint128::one();
+    }
+
+    position.total -= amount;
+    position.withdrawn -= withdrawn_moved;
+
+    Position {
+        total: amount,
+        withdrawn: withdrawn_moved,
+        vest_schedule: position.vest_schedule.clone(),
+        terminated: position.terminated,
+        held_back: position.held_back + SPLIT_ROUNDING,
+    }
+}
+
+/// Add a position's totals and withdrawn amounts to another one with the same vesting schedule.
+///
+/// The merged position holds back what both positions did and more, so that it never releases more
+/// than the two would have separately.
+pub fn merge(into: &mut Position, position: &Position) {
+    into.total += position.total;
+    into.withdrawn += position.withdrawn;
+    into.held_back += position.held_back + MERGE_ROUNDING;
+}
+
+/// The position's status at the current block
+pub fn compute_status(block: &BlockInfo, position: &Position) -> PositionStatus {
+    match status_key(position) {
+        STATUS_FULLY_WITHDRAWN => PositionStatus::FullyWithdrawn,
+        STATUS_TERMINATED => PositionStatus::Terminated,
+        _ => {
+            let vest = &position.vest_schedule;
+            if vest.unit.current(block) >= vest.start_time + vest.duration {
+                PositionStatus::FullyVested
+            } else {
+                PositionStatus::Active
+            }
+        }
+    }
+}
+
 pub fn compute_position_response(
-    time: u64,
+    block: &BlockInfo,
     user: impl Into<String>,
     position: &Position,
     unlock_schedule: &Schedule,
 ) -> PositionResponse {
     let (vested, unlocked, withdrawable) = compute_withdrawable(
-        time,
+        block,
         position.total,
         position.withdrawn,
+        position.held_back,
         &position.vest_schedule,
         unlock_schedule,
     );
 
+    let vest_schedule = &position.vest_schedule;
+    let cliff_amount = compute_released(
+        vest_schedule,
+        position.total,
+        vest_schedule.start_time + vest_schedule.cliff,
+    );
+
     PositionResponse {
         user: user.into(),
         total: position.total,
+        cliff_amount,
         vested,
         unlocked,
         withdrawn: position.withdrawn,
         withdrawable,
         vest_schedule: position.vest_schedule.clone(),
+        status: compute_status(block, position),
     }
 }
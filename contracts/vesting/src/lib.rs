@@ -1,6 +1,8 @@
 pub mod contract;
+pub mod cw721;
 pub mod error;
 pub mod helpers;
 pub mod migrations;
 pub mod msg;
 pub mod state;
+pub mod stream;
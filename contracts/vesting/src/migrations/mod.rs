@@ -1,3 +1,4 @@
 pub mod v1_1_0;
 pub mod v1_1_1;
 pub mod v1_1_2;
+pub mod v1_2_0;
@@ -1,14 +1,10 @@
 use cosmwasm_std::{DepsMut, Response};
 use cw2::set_contract_version;
 
-use crate::{
-    contract::{CONTRACT_NAME, CONTRACT_VERSION},
-    error::Result,
-    msg::Config,
-    state::CONFIG,
-};
+use crate::{contract::CONTRACT_NAME, error::Result, msg::Config, state::CONFIG};
 
 const FROM_VERSION: &str = "1.0.0";
+const TO_VERSION: &str = "1.1.0";
 
 pub mod v1_0_0_state {
     use cosmwasm_std::Addr;
@@ -30,14 +26,17 @@ pub fn migrate(deps: DepsMut) -> Result<Response> {
         owner: v1_0_0_state::OWNER.load(deps.storage)?,
         denom: v1_0_0_state::VEST_DENOM.into(),
         unlock_schedule: v1_0_0_state::UNLOCK_SCHEDULE.load(deps.storage)?,
+        timelock: 0,
+        emergency_release: Default::default(),
+        nft_contract: None,
     };
 
     CONFIG.save(deps.storage, &cfg)?;
 
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, TO_VERSION)?;
 
     Ok(Response::new()
         .add_attribute("action", "migrate")
         .add_attribute("from_version", FROM_VERSION)
-        .add_attribute("to_version", CONTRACT_VERSION))
+        .add_attribute("to_version", TO_VERSION))
 }
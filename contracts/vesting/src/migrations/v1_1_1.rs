@@ -2,13 +2,14 @@ use cosmwasm_std::{coins, BankMsg, CosmosMsg, DepsMut, Response, Uint128};
 use cw2::set_contract_version;
 
 use crate::{
-    contract::{CONTRACT_NAME, CONTRACT_VERSION},
+    contract::CONTRACT_NAME,
     error::Result,
     msg::V1_1_1Updates,
     state::{CONFIG, POSITIONS},
 };
 
 const FROM_VERSION: &str = "1.1.0";
+const TO_VERSION: &str = "1.1.1";
 
 pub fn migrate(deps: DepsMut, msg: V1_1_1Updates) -> Result<Response> {
     // make sure we're migrating the correct contract and from the correct version
@@ -36,7 +37,7 @@ pub fn migrate(deps: DepsMut, msg: V1_1_1Updates) -> Result<Response> {
     // Additoinal check that the total amount reclaimed back is as expected
     assert!(total_reclaim == msg.total_reclaim);
 
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, TO_VERSION)?;
 
     let cfg = CONFIG.load(deps.storage)?;
 
@@ -47,5 +48,5 @@ pub fn migrate(deps: DepsMut, msg: V1_1_1Updates) -> Result<Response> {
         }))
         .add_attribute("action", "migrate")
         .add_attribute("from_version", FROM_VERSION)
-        .add_attribute("to_version", CONTRACT_VERSION))
+        .add_attribute("to_version", TO_VERSION))
 }
@@ -2,19 +2,20 @@ use cosmwasm_std::{coins, BankMsg, CosmosMsg, DepsMut, Env, Response};
 use cw2::set_contract_version;
 
 use crate::{
-    contract::{CONTRACT_NAME, CONTRACT_VERSION},
+    contract::CONTRACT_NAME,
     error::Result,
     msg::V1_1_2Updates,
     state::{CONFIG, WITHDRAW_ENABLED},
 };
 
 const FROM_VERSION: &str = "1.1.1";
+const TO_VERSION: &str = "1.1.2";
 
 pub fn migrate(deps: DepsMut, env: Env, msg: V1_1_2Updates) -> Result<Response> {
     // Make sure we're migrating the correct contract and from the correct version
     cw2::assert_contract_version(deps.as_ref().storage, CONTRACT_NAME, FROM_VERSION)?;
 
-    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, TO_VERSION)?;
 
     // Disable withdraws during migration
     WITHDRAW_ENABLED.save(deps.storage, &false)?;
@@ -30,5 +31,5 @@ pub fn migrate(deps: DepsMut, env: Env, msg: V1_1_2Updates) -> Result<Response>
         }))
         .add_attribute("action", "migrate")
         .add_attribute("from_version", FROM_VERSION)
-        .add_attribute("to_version", CONTRACT_VERSION))
+        .add_attribute("to_version", TO_VERSION))
 }
@@ -0,0 +1,35 @@
+use cosmwasm_std::{DepsMut, Order, Response, StdResult};
+use cw2::set_contract_version;
+
+use crate::{contract::CONTRACT_NAME, error::Result, state::POSITIONS};
+
+const FROM_VERSION: &str = "1.1.2";
+const TO_VERSION: &str = "1.2.0";
+
+pub fn migrate(deps: DepsMut) -> Result<Response> {
+    // Make sure we're migrating the correct contract and from the correct version
+    cw2::assert_contract_version(deps.as_ref().storage, CONTRACT_NAME, FROM_VERSION)?;
+
+    set_contract_version(deps.storage, CONTRACT_NAME, TO_VERSION)?;
+
+    // Positions are stored under the same namespace as before, but their indexes don't exist yet.
+    // Re-saving each position builds them.
+    //
+    // Positions terminated before this version can't be told apart from ones whose schedule simply
+    // ended early, so they load with `terminated: false` and are indexed as vesting. Since their
+    // schedules end at the time of termination, they're reported as fully vested rather than
+    // terminated.
+    let positions = POSITIONS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for (user_addr, position) in &positions {
+        POSITIONS.save(deps.storage, user_addr, position)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", FROM_VERSION)
+        .add_attribute("to_version", TO_VERSION)
+        .add_attribute("positions_indexed", positions.len().to_string()))
+}
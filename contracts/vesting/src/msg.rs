@@ -1,7 +1,110 @@
+use std::fmt;
+
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Api, StdResult, Uint128};
+use cosmwasm_std::{Addr, Api, BlockInfo, Decimal, StdResult, Uint128};
+pub use mars_pagination::{OrderBy, Paginated};
+
+#[cw_serde]
+#[derive(Copy, Default, Eq)]
+pub enum TimeUnit {
+    /// Schedule is expressed in UNIX seconds
+    #[default]
+    Seconds,
+    /// Schedule is expressed in block heights
+    Blocks,
+}
+
+impl TimeUnit {
+    /// The current point in time, expressed in this unit
+    pub fn current(&self, block: &BlockInfo) -> u64 {
+        match self {
+            TimeUnit::Seconds => block.time.seconds(),
+            TimeUnit::Blocks => block.height,
+        }
+    }
+}
+
+/// A point on a piecewise-linear curve, expressed as fractions of the schedule's duration and of the
+/// total amount
+#[cw_serde]
+pub struct CurvePoint {
+    pub time: Decimal,
+    pub amount: Decimal,
+}
 
+/// Shape of a schedule between the end of its cliff and the end of its duration
 #[cw_serde]
+#[derive(Default)]
+pub enum Curve {
+    /// Tokens are vested/unlocked at a constant rate
+    #[default]
+    Linear,
+    /// The fraction of tokens vested/unlocked is the fraction of the duration elapsed raised to the
+    /// given power; i.e. back-loaded for exponents greater than 1
+    Power {
+        exponent: u32,
+    },
+    /// Mirror image of `Power`: one minus the fraction of the duration remaining raised to the given
+    /// power; i.e. front-loaded for exponents greater than 1
+    InversePower {
+        exponent: u32,
+    },
+    /// The fraction of tokens vested/unlocked is interpolated linearly between the given points. The
+    /// curve implicitly starts at (0, 0) and ends at (1, 1).
+    PiecewiseLinear {
+        points: Vec<CurvePoint>,
+    },
+}
+
+impl Curve {
+    /// The fraction of tokens vested/unlocked once the given fraction of the duration has elapsed.
+    ///
+    /// Results are rounded down, and are monotonic in `elapsed` provided that the curve is valid.
+    pub fn fraction(&self, elapsed: Decimal) -> Decimal {
+        match self {
+            Curve::Linear => elapsed,
+            Curve::Power {
+                exponent,
+            } => elapsed.pow(*exponent),
+            Curve::InversePower {
+                exponent,
+            } => Decimal::one() - (Decimal::one() - elapsed).pow(*exponent),
+            Curve::PiecewiseLinear {
+                points,
+            } => {
+                let origin = CurvePoint {
+                    time: Decimal::zero(),
+                    amount: Decimal::zero(),
+                };
+                let end = CurvePoint {
+                    time: Decimal::one(),
+                    amount: Decimal::one(),
+                };
+
+                // find the segment containing the elapsed fraction
+                let mut from = &origin;
+                let mut to = &end;
+                for point in points {
+                    if point.time > elapsed {
+                        to = point;
+                        break;
+                    }
+                    from = point;
+                }
+
+                if to.time == from.time {
+                    return to.amount;
+                }
+
+                from.amount
+                    + (to.amount - from.amount) * ((elapsed - from.time) / (to.time - from.time))
+            }
+        }
+    }
+}
+
+#[cw_serde]
+#[derive(Default)]
 pub struct Schedule {
     /// Time when vesting/unlocking starts
     pub start_time: u64,
@@ -10,6 +113,20 @@ pub struct Schedule {
     /// Duration of the vesting/unlocking process. At time `start_time + duration`, the tokens are
     /// vested/unlocked in full
     pub duration: u64,
+    /// Unit in which the above times are expressed
+    #[serde(default)]
+    pub unit: TimeUnit,
+    /// Fraction of the total to be released as a lump sum at the end of the cliff period. If not
+    /// provided, the amount released at the cliff is implied by the curve.
+    #[serde(default)]
+    pub cliff_percent: Option<Decimal>,
+    /// Time from which the amount not released at the cliff starts to vest/unlock along the curve;
+    /// `start_time` if not provided
+    #[serde(default)]
+    pub linear_start: Option<u64>,
+    /// How tokens are vested/unlocked over the duration
+    #[serde(default)]
+    pub curve: Curve,
 }
 
 #[cw_serde]
@@ -20,6 +137,41 @@ pub struct Position {
     pub withdrawn: Uint128,
     /// The user's vesting schedule
     pub vest_schedule: Schedule,
+    /// Whether the position has been terminated
+    #[serde(default)]
+    pub terminated: bool,
+    /// Amount held back from the vested and unlocked amounts until they reach the total, so that
+    /// splitting and merging positions never releases tokens earlier due to rounding
+    #[serde(default)]
+    pub held_back: Uint128,
+}
+
+/// Lifecycle stage of a vesting position
+#[cw_serde]
+#[derive(Copy, Eq)]
+pub enum PositionStatus {
+    /// Tokens are still vesting
+    Active,
+    /// All tokens have vested, but not all have been withdrawn
+    FullyVested,
+    /// The position has been terminated, but not all of its vested tokens have been withdrawn
+    Terminated,
+    /// All tokens have been withdrawn
+    FullyWithdrawn,
+}
+
+#[cw_serde]
+pub enum PositionFilter {
+    /// Positions whose vesting starts within the given range, inclusive of both ends. The range is
+    /// compared against `start_time` as is, regardless of the schedule's time unit.
+    StartTime {
+        min: Option<u64>,
+        max: Option<u64>,
+    },
+    /// Positions currently in the given status
+    Status {
+        status: PositionStatus,
+    },
 }
 
 #[cw_serde]
@@ -30,6 +182,16 @@ pub struct Config<T> {
     pub denom: String,
     /// Schedule for token unlocking; this schedule is the same for all users
     pub unlock_schedule: Schedule,
+    /// Minimum delay, in seconds, between a config change being proposed and it being executable
+    #[serde(default)]
+    pub timelock: u64,
+    /// What recipients can claim once the contract has been shut down in an emergency
+    #[serde(default)]
+    pub emergency_release: EmergencyRelease,
+    /// CW721 contract that represents vesting positions as NFTs. If provided, the contract must
+    /// have this contract set as its minter; each position created is then minted as an NFT, and
+    /// can only be withdrawn from by the NFT's current owner.
+    pub nft_contract: Option<T>,
 }
 
 impl Config<String> {
@@ -38,6 +200,9 @@ impl Config<String> {
             owner: api.addr_validate(&self.owner)?,
             denom: self.denom,
             unlock_schedule: self.unlock_schedule,
+            timelock: self.timelock,
+            emergency_release: self.emergency_release,
+            nft_contract: self.nft_contract.map(|addr| api.addr_validate(&addr)).transpose()?,
         })
     }
 }
@@ -48,16 +213,93 @@ impl From<Config<Addr>> for Config<String> {
             owner: cfg.owner.into(),
             denom: cfg.denom,
             unlock_schedule: cfg.unlock_schedule,
+            timelock: cfg.timelock,
+            emergency_release: cfg.emergency_release,
+            nft_contract: cfg.nft_contract.map(Into::into),
         }
     }
 }
 
+#[cw_serde]
+#[derive(Copy, Default, Eq)]
+pub enum EmergencyRelease {
+    /// Recipients can claim their vested amount immediately, regardless of the unlock schedule
+    Vested,
+    /// Recipients can claim their vested amount as it is unlocked according to the unlock schedule
+    #[default]
+    VestedAndUnlocked,
+}
+
+#[cw_serde]
+pub struct EmergencyState {
+    /// Time when the emergency shutdown was triggered; vesting stops at this time
+    pub triggered_at: u64,
+    /// Block height at which the emergency shutdown was triggered; vesting of positions whose
+    /// schedules are expressed in blocks stops at this height
+    pub triggered_height: u64,
+    /// Address that receives the unvested tokens
+    pub recovery_addr: String,
+    /// Total amount of unvested tokens sent to the recovery address so far
+    pub recovered: Uint128,
+}
+
+#[cw_serde]
+pub struct PendingConfig<T> {
+    /// The proposed configurations
+    pub new_cfg: Config<T>,
+    /// Time after which the proposed configurations can be executed
+    pub executable_at: u64,
+}
+
+impl From<PendingConfig<Addr>> for PendingConfig<String> {
+    fn from(pending: PendingConfig<Addr>) -> Self {
+        PendingConfig {
+            new_cfg: pending.new_cfg.into(),
+            executable_at: pending.executable_at,
+        }
+    }
+}
+
+/// Privileges that can be granted to an address.
+///
+/// The contract owner, as well as any address holding the `Admin` role, implicitly holds every
+/// role.
+#[cw_serde]
+#[derive(Copy, Eq)]
+pub enum Role {
+    /// Can update the config, and grant or revoke roles
+    Admin,
+    /// Can create vesting positions and payment streams
+    Creator,
+    /// Can terminate vesting positions
+    Terminator,
+    /// Can enable or disable withdrawals
+    Pauser,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Role::Admin => "admin",
+            Role::Creator => "creator",
+            Role::Terminator => "terminator",
+            Role::Pauser => "pauser",
+        };
+        write!(f, "{s}")
+    }
+}
+
 #[cw_serde]
 pub enum ExecuteMsg {
-    /// Update the contract's configurations
-    UpdateConfig {
+    /// Propose an update to the contract's configurations, to be executable once the timelock has
+    /// elapsed. Replaces any change already pending.
+    ProposeConfig {
         new_cfg: Config<String>,
     },
+    /// Apply the pending config change; can be invoked by anyone once the timelock has elapsed
+    ExecuteConfig {},
+    /// Cancel the pending config change
+    CancelConfig {},
     /// Create a new vesting position for a user
     CreatePosition {
         user: String,
@@ -69,28 +311,162 @@ pub enum ExecuteMsg {
     },
     /// Withdraw vested and unlocked MARS tokens
     Withdraw {},
+    /// Withdraw vested and unlocked MARS tokens from a position represented by an NFT, as the NFT's
+    /// current owner
+    WithdrawToken {
+        token_id: String,
+    },
+    /// Authorize an operator to withdraw from the caller's position. Replaces any existing
+    /// authorization of the same operator.
+    GrantWithdrawAuthorization {
+        operator: String,
+        /// Time after which the authorization is no longer valid, as UNIX timestamp; never expires
+        /// if not provided
+        expiry: Option<u64>,
+        /// Maximum total amount the operator can withdraw; unlimited if not provided
+        max_amount: Option<Uint128>,
+        /// Address to receive the withdrawn tokens; the position holder if not provided
+        recipient: Option<String>,
+    },
+    /// Revoke an operator's authorization to withdraw from the caller's position
+    RevokeWithdrawAuthorization {
+        operator: String,
+    },
+    /// Withdraw vested and unlocked MARS tokens from a user's position, as an authorized operator
+    WithdrawOnBehalf {
+        user: String,
+    },
+    /// Move part of the caller's position to a new position for another user, with the same
+    /// vesting schedule. The withdrawn amount is divided in proportion to the totals. To make up for
+    /// rounding, the new position vests and unlocks up to a unit later until it's fully released.
+    SplitPosition {
+        /// Amount of the caller's total allocation to be moved to the new position
+        amount: Uint128,
+        new_user: String,
+    },
+    /// Merge the caller's position into another user's position with an identical vesting
+    /// schedule. The caller's position is removed.
+    ///
+    /// The other user's consent isn't required: merging adds the caller's total and withdrawn
+    /// amounts to theirs. To make up for rounding, the merged position vests and unlocks up to a
+    /// few units later than the two positions would have separately, but never earlier; the
+    /// difference is released once the position is fully vested and unlocked.
+    MergePositions {
+        into: String,
+    },
+    /// Opt in or out of having withdrawable tokens pushed to the caller by `Distribute`
+    SetAutoDistribute {
+        enabled: bool,
+    },
+    /// Send withdrawable tokens to the holders of positions that opted in to auto-distribution.
+    ///
+    /// Can be invoked by anyone. Positions are processed in batches of `limit`, which must not be
+    /// zero; if `start_after` is not provided, processing resumes after the last position of the
+    /// previous such batch, so that repeated invocations sweep through all positions. Batches
+    /// starting after an explicit `start_after` don't move the sweep.
+    Distribute {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Open a payment stream to a recipient, funded by the tokens sent along with the message
+    CreateStream {
+        recipient: String,
+        /// Amount of tokens to be streamed per second
+        rate: Uint128,
+    },
+    /// Add the tokens sent along with the message to a stream's funding, as its payer
+    TopUpStream {
+        id: u64,
+    },
+    /// Pause or resume a stream, as its payer. No tokens are streamed while a stream is paused.
+    SetStreamPaused {
+        id: u64,
+        paused: bool,
+    },
+    /// Close a stream, as its payer. Tokens already streamed are sent to the recipient, and the
+    /// unstreamed balance is refunded to the payer.
+    CancelStream {
+        id: u64,
+    },
+    /// Withdraw the tokens streamed so far, as a stream's recipient
+    WithdrawStream {
+        id: u64,
+    },
+    /// Grant a role to an address
+    GrantRole {
+        user: String,
+        role: Role,
+    },
+    /// Revoke a role from an address
+    RevokeRole {
+        user: String,
+        role: Role,
+    },
+    /// Enable or disable withdrawals
+    SetWithdrawEnabled {
+        enabled: bool,
+    },
+    /// Send tokens held by the contract in excess of its liabilities to the recipient
+    SweepSurplus {
+        recipient: String,
+    },
+    /// Permanently shut down the contract: vesting stops for all positions, and all executions
+    /// other than `EmergencyRecover`, `EmergencyWithdraw`, `EmergencyWithdrawToken`,
+    /// `CancelStream` and `WithdrawStream` are frozen. The unvested tokens are sent to the recovery
+    /// address as positions are terminated, by `EmergencyRecover` or by the holders' emergency
+    /// withdrawals.
+    EmergencyShutdown {
+        recovery_addr: String,
+    },
+    /// Following an emergency shutdown, terminate positions as of the time of the shutdown, sending
+    /// their unvested tokens to the recovery address.
+    ///
+    /// Can be invoked by anyone. Positions are processed in batches of `limit`, which must not be
+    /// zero, each invocation resuming after the last position of the previous batch.
+    EmergencyRecover {
+        limit: Option<u32>,
+    },
+    /// Following an emergency shutdown, withdraw tokens according to the config's
+    /// `emergency_release`. Not affected by withdrawals being disabled.
+    EmergencyWithdraw {},
+    /// Same as `EmergencyWithdraw`, but from a position represented by an NFT, as the NFT's
+    /// current owner
+    EmergencyWithdrawToken {
+        token_id: String,
+    },
 }
 
+/// List queries return their results one page at a time. Results lie strictly between the
+/// `start_after` and `start_before` cursors, if provided, and are returned in the given order,
+/// ascending by default. Each page includes the cursor from which to request the next one.
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     /// The contract's configurations
     #[returns(Config<String>)]
     Config {},
-    /// Amount of MARS tokens of a vesting recipient current locked in the contract
+    /// The config change currently queued, if any
+    #[returns(Option<PendingConfig<String>>)]
+    PendingConfig {},
+    /// Amount of MARS tokens of a vesting recipient current locked in the contract.
+    ///
+    /// Positions represented by NFTs count towards the user they were created for, not the NFT's
+    /// current owner.
     #[returns(VotingPowerResponse)]
     VotingPower {
         user: String,
     },
     /// Enumerate all vesting recipients and return their current voting power
-    #[returns(Vec<VotingPowerResponse>)]
+    #[returns(Paginated<VotingPowerResponse>)]
     VotingPowers {
         start_after: Option<String>,
+        start_before: Option<String>,
         limit: Option<u32>,
+        order: Option<OrderBy>,
     },
     /// Details of a recipient's vesting position
     ///
-    /// NOTE: This query depends on block time, therefore it may not work with time travel queries.
+    /// NOTE: This query depends on block time and height, therefore it may not work with time travel queries.
     /// In such cases, use WASM raw query instead.
     #[returns(PositionResponse)]
     Position {
@@ -98,12 +474,74 @@ pub enum QueryMsg {
     },
     /// Enumerate all vesting positions
     ///
-    /// NOTE: This query depends on block time, therefore it may not work with time travel queries.
+    /// NOTE: This query depends on block time and height, therefore it may not work with time travel queries.
     /// In such cases, use WASM raw query instead.
-    #[returns(Vec<PositionResponse>)]
+    #[returns(Paginated<PositionResponse>)]
     Positions {
         start_after: Option<String>,
+        start_before: Option<String>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+    /// Enumerate vesting positions matching a filter.
+    ///
+    /// Positions are ordered by start time when filtering by start time, and by address when
+    /// filtering by status. Either way, the cursors are addresses of positions.
+    ///
+    /// NOTE: This query depends on block time and height, therefore it may not work with time travel queries.
+    /// In such cases, use WASM raw query instead.
+    #[returns(Paginated<PositionResponse>)]
+    PositionsBy {
+        filter: PositionFilter,
+        start_after: Option<String>,
+        start_before: Option<String>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+    /// Details of the emergency shutdown, if one has been triggered
+    #[returns(Option<EmergencyState>)]
+    EmergencyState {},
+    /// The contract's token balance compared to the amount it owes vesting recipients
+    #[returns(SolvencyResponse)]
+    Solvency {},
+    /// Enumerate the operators currently authorized to withdraw from a user's position
+    ///
+    /// NOTE: This query depends on block time, therefore it may not work with time travel queries.
+    /// In such cases, use WASM raw query instead.
+    #[returns(Paginated<WithdrawAuthorizationResponse>)]
+    WithdrawAuthorizations {
+        user: String,
+        start_after: Option<String>,
+        start_before: Option<String>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+    /// Details of a payment stream
+    ///
+    /// NOTE: This query depends on block time, therefore it may not work with time travel queries.
+    /// In such cases, use WASM raw query instead.
+    #[returns(StreamResponse)]
+    Stream {
+        id: u64,
+    },
+    /// Enumerate all payment streams
+    ///
+    /// NOTE: This query depends on block time, therefore it may not work with time travel queries.
+    /// In such cases, use WASM raw query instead.
+    #[returns(Paginated<StreamResponse, u64>)]
+    Streams {
+        start_after: Option<u64>,
+        start_before: Option<u64>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+    /// Enumerate all addresses that have been granted roles
+    #[returns(Paginated<RolesResponse>)]
+    Roles {
+        start_after: Option<String>,
+        start_before: Option<String>,
         limit: Option<u32>,
+        order: Option<OrderBy>,
     },
 }
 
@@ -121,6 +559,8 @@ pub struct PositionResponse {
     pub user: String,
     /// Total amount of MARS tokens allocated to this recipient
     pub total: Uint128,
+    /// Amount of tokens that vest at the end of the cliff period, according to the vesting schedule
+    pub cliff_amount: Uint128,
     /// Amount of tokens that have been vested, according to the vesting schedule
     pub vested: Uint128,
     /// Amount of tokens that have been unlocked, according to the unlocking schedule
@@ -132,6 +572,108 @@ pub struct PositionResponse {
     pub withdrawable: Uint128,
     /// This vesting position's vesting schedule
     pub vest_schedule: Schedule,
+    /// The position's current status
+    pub status: PositionStatus,
+}
+
+#[cw_serde]
+pub struct WithdrawAuthorization {
+    /// Time after which the authorization is no longer valid
+    pub expiry: Option<u64>,
+    /// Remaining amount the operator can withdraw; unlimited if `None`
+    pub max_amount: Option<Uint128>,
+    /// Address to receive the withdrawn tokens; the position holder if `None`
+    pub recipient: Option<Addr>,
+}
+
+impl WithdrawAuthorization {
+    pub fn is_expired(&self, time: u64) -> bool {
+        self.expiry.is_some_and(|expiry| time >= expiry)
+    }
+}
+
+/// An open-ended payment stream, releasing tokens to the recipient at a constant rate for as long as
+/// it is funded and not paused
+#[cw_serde]
+pub struct Stream {
+    /// Address that funds the stream, and to which unstreamed tokens are refunded upon cancellation
+    pub payer: Addr,
+    /// Address to receive the streamed tokens
+    pub recipient: Addr,
+    /// Amount of tokens streamed per second
+    pub rate: Uint128,
+    /// Amount of tokens funded but not yet streamed
+    pub remaining: Uint128,
+    /// Amount of tokens streamed but not yet withdrawn
+    pub accrued: Uint128,
+    /// Time up to which the stream has been accrued
+    pub last_accrued: u64,
+    /// Whether streaming is currently paused
+    pub paused: bool,
+}
+
+impl Stream {
+    /// Move the tokens streamed since the last accrual from the remaining to the accrued amount.
+    pub fn accrue(&mut self, time: u64) {
+        if !self.paused {
+            let elapsed = time.saturating_sub(self.last_accrued);
+            let streamed = self.rate.saturating_mul(Uint128::from(elapsed)).min(self.remaining);
+            self.remaining -= streamed;
+            self.accrued += streamed;
+        }
+        self.last_accrued = self.last_accrued.max(time);
+    }
+}
+
+#[cw_serde]
+pub struct StreamResponse {
+    /// Identifier of the stream
+    pub id: u64,
+    /// Address that funds the stream
+    pub payer: String,
+    /// Address to receive the streamed tokens
+    pub recipient: String,
+    /// Amount of tokens streamed per second
+    pub rate: Uint128,
+    /// Amount of tokens funded but not yet streamed
+    pub remaining: Uint128,
+    /// Amount of tokens that can be withdrawn by the recipient now
+    pub withdrawable: Uint128,
+    /// Whether streaming is currently paused
+    pub paused: bool,
+}
+
+#[cw_serde]
+pub struct WithdrawAuthorizationResponse {
+    /// Address of the operator
+    pub operator: String,
+    /// Time after which the authorization is no longer valid
+    pub expiry: Option<u64>,
+    /// Remaining amount the operator can withdraw; unlimited if `None`
+    pub max_amount: Option<Uint128>,
+    /// Address to receive the withdrawn tokens; the position holder if `None`
+    pub recipient: Option<String>,
+}
+
+#[cw_serde]
+pub struct SolvencyResponse {
+    /// Amount of tokens held by the contract
+    pub balance: Uint128,
+    /// Amount of tokens allocated to vesting positions or funding payment streams, but not yet
+    /// withdrawn
+    pub liabilities: Uint128,
+    /// Amount by which the balance exceeds the liabilities
+    pub surplus: Uint128,
+    /// Amount by which the liabilities exceed the balance
+    pub deficit: Uint128,
+}
+
+#[cw_serde]
+pub struct RolesResponse {
+    /// Address of the user
+    pub user: String,
+    /// Roles granted to this user
+    pub roles: Vec<Role>,
 }
 
 #[cw_serde]
@@ -139,6 +681,7 @@ pub enum MigrateMsg {
     V1_0_0ToV1_1_0 {},
     V1_1_0ToV1_1_1(V1_1_1Updates),
     V1_1_1ToV1_1_2(V1_1_2Updates),
+    V1_1_2ToV1_2_0 {},
 }
 
 #[cw_serde]
@@ -1,10 +1,85 @@
-use cosmwasm_std::Addr;
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Empty};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
-use crate::msg::{Config, Position};
+use crate::msg::{
+    Config, EmergencyState, PendingConfig, Position, Role, Stream, WithdrawAuthorization,
+};
 
 pub const CONFIG: Item<Config<Addr>> = Item::new("config");
 
-pub const POSITIONS: Map<&Addr, Position> = Map::new("positions");
+pub const PENDING_CONFIG: Item<PendingConfig<Addr>> = Item::new("pending_config");
+
+pub struct PositionIndexes<'a> {
+    /// Positions by the start time of their vesting schedule
+    pub start_time: MultiIndex<'a, u64, Position, Addr>,
+    /// Positions by their status key; see `status_key`
+    pub status: MultiIndex<'a, u8, Position, Addr>,
+}
+
+impl<'a> IndexList<Position> for PositionIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Position>> + '_> {
+        let v: Vec<&dyn Index<Position>> = vec![&self.start_time, &self.status];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Status keys of positions in the status index. Whether a position is fully vested depends on the
+/// current time, so active and fully vested positions share a key.
+pub const STATUS_VESTING: u8 = 0;
+pub const STATUS_TERMINATED: u8 = 1;
+pub const STATUS_FULLY_WITHDRAWN: u8 = 2;
+
+pub fn status_key(position: &Position) -> u8 {
+    if position.withdrawn >= position.total {
+        STATUS_FULLY_WITHDRAWN
+    } else if position.terminated {
+        STATUS_TERMINATED
+    } else {
+        STATUS_VESTING
+    }
+}
+
+pub const POSITIONS: IndexedMap<&Addr, Position, PositionIndexes> = IndexedMap::new(
+    "positions",
+    PositionIndexes {
+        start_time: MultiIndex::new(
+            |_, position| position.vest_schedule.start_time,
+            "positions",
+            "positions__start_time",
+        ),
+        status: MultiIndex::new(
+            |_, position| status_key(position),
+            "positions",
+            "positions__status",
+        ),
+    },
+);
 
 pub const WITHDRAW_ENABLED: Item<bool> = Item::new("withdraw_enabled");
+
+pub const ROLES: Map<&Addr, Vec<Role>> = Map::new("roles");
+
+pub const EMERGENCY: Item<EmergencyState> = Item::new("emergency");
+
+/// The last position terminated by `EmergencyRecover`
+pub const EMERGENCY_CURSOR: Item<Addr> = Item::new("emergency_cursor");
+
+/// Withdraw authorizations, keyed by position holder and operator
+pub const WITHDRAW_AUTHORIZATIONS: Map<(&Addr, &Addr), WithdrawAuthorization> =
+    Map::new("withdraw_authorizations");
+
+/// Position holders who opted in to having their withdrawable tokens pushed to them
+pub const AUTO_DISTRIBUTE: Map<&Addr, Empty> = Map::new("auto_distribute");
+
+/// The last position processed by `Distribute`
+pub const DISTRIBUTE_CURSOR: Item<Addr> = Item::new("distribute_cursor");
+
+/// Positions represented by NFTs, and the CW721 contract of each. The NFT's token ID is the address
+/// of the user the position was created for.
+pub const TOKENIZED_POSITIONS: Map<&Addr, Addr> = Map::new("tokenized_positions");
+
+/// Payment streams, keyed by ID
+pub const STREAMS: Map<u64, Stream> = Map::new("streams");
+
+/// The ID of the most recently created stream
+pub const STREAM_COUNT: Item<u64> = Item::new("stream_count");
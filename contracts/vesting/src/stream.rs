@@ -0,0 +1,216 @@
+use cosmwasm_std::{
+    coins, Addr, BankMsg, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Order, Response, Uint128,
+};
+use cw_utils::must_pay;
+use mars_pagination as pagination;
+
+use crate::{
+    error::{Error, Result},
+    helpers::assert_role,
+    msg::{OrderBy, Paginated, Role, Stream, StreamResponse},
+    state::{CONFIG, STREAMS, STREAM_COUNT, WITHDRAW_ENABLED},
+};
+
+//--------------------------------------------------------------------------------------------------
+// Executions
+//--------------------------------------------------------------------------------------------------
+
+pub fn create_stream(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient_addr: Addr,
+    rate: Uint128,
+) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // only creators can open streams
+    assert_role(deps.storage, &cfg, &info.sender, Role::Creator)?;
+
+    if rate.is_zero() {
+        return Err(Error::InvalidStreamRate);
+    }
+
+    let deposit = must_pay(&info, &cfg.denom)?;
+
+    let id = STREAM_COUNT.may_load(deps.storage)?.unwrap_or(0) + 1;
+    STREAM_COUNT.save(deps.storage, &id)?;
+
+    STREAMS.save(
+        deps.storage,
+        id,
+        &Stream {
+            payer: info.sender.clone(),
+            recipient: recipient_addr.clone(),
+            rate,
+            remaining: deposit,
+            accrued: Uint128::zero(),
+            last_accrued: env.block.time.seconds(),
+            paused: false,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/create_stream")
+        .add_attribute("id", id.to_string())
+        .add_attribute("payer", info.sender)
+        .add_attribute("recipient", recipient_addr)
+        .add_attribute("rate", rate)
+        .add_attribute("deposit", deposit))
+}
+
+pub fn top_up_stream(deps: DepsMut, env: Env, info: MessageInfo, id: u64) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut stream = load_stream_as_payer(deps.as_ref(), id, &info.sender)?;
+
+    let deposit = must_pay(&info, &cfg.denom)?;
+
+    // accrue first, so that a stream that ran dry doesn't retroactively stream the top-up
+    stream.accrue(env.block.time.seconds());
+    stream.remaining += deposit;
+    STREAMS.save(deps.storage, id, &stream)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/top_up_stream")
+        .add_attribute("id", id.to_string())
+        .add_attribute("deposit", deposit)
+        .add_attribute("remaining", stream.remaining))
+}
+
+pub fn set_stream_paused(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: u64,
+    paused: bool,
+) -> Result<Response> {
+    let mut stream = load_stream_as_payer(deps.as_ref(), id, &info.sender)?;
+
+    stream.accrue(env.block.time.seconds());
+    stream.paused = paused;
+    STREAMS.save(deps.storage, id, &stream)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "mars/vesting/set_stream_paused")
+        .add_attribute("id", id.to_string())
+        .add_attribute("paused", paused.to_string()))
+}
+
+pub fn cancel_stream(deps: DepsMut, env: Env, info: MessageInfo, id: u64) -> Result<Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut stream = load_stream_as_payer(deps.as_ref(), id, &info.sender)?;
+
+    stream.accrue(env.block.time.seconds());
+    STREAMS.remove(deps.storage, id);
+
+    let mut msgs = vec![];
+    if !stream.accrued.is_zero() {
+        msgs.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: stream.recipient.to_string(),
+            amount: coins(stream.accrued.u128(), &cfg.denom),
+        }));
+    }
+    if !stream.remaining.is_zero() {
+        msgs.push(CosmosMsg::Bank(BankMsg::Send {
+            to_address: stream.payer.to_string(),
+            amount: coins(stream.remaining.u128(), &cfg.denom),
+        }));
+    }
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("action", "mars/vesting/cancel_stream")
+        .add_attribute("id", id.to_string())
+        .add_attribute("streamed", stream.accrued)
+        .add_attribute("refunded", stream.remaining))
+}
+
+pub fn withdraw_stream(deps: DepsMut, env: Env, info: MessageInfo, id: u64) -> Result<Response> {
+    let withdraw_enabled = WITHDRAW_ENABLED.may_load(deps.storage)?.unwrap_or(true);
+    if !withdraw_enabled {
+        return Err(Error::WithdrawDisabled);
+    }
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let mut stream = STREAMS.load(deps.storage, id)?;
+
+    if info.sender != stream.recipient {
+        return Err(Error::NotStreamRecipient);
+    }
+
+    stream.accrue(env.block.time.seconds());
+
+    let withdrawable = stream.accrued;
+    if withdrawable.is_zero() {
+        return Err(Error::ZeroWithdrawable);
+    }
+
+    stream.accrued = Uint128::zero();
+    STREAMS.save(deps.storage, id, &stream)?;
+
+    Ok(Response::new()
+        .add_message(CosmosMsg::Bank(BankMsg::Send {
+            to_address: stream.recipient.to_string(),
+            amount: coins(withdrawable.u128(), cfg.denom),
+        }))
+        .add_attribute("action", "mars/vesting/withdraw_stream")
+        .add_attribute("id", id.to_string())
+        .add_attribute("recipient", stream.recipient)
+        .add_attribute("timestamp", env.block.time.seconds().to_string())
+        .add_attribute("withdrawable", withdrawable))
+}
+
+fn load_stream_as_payer(deps: Deps, id: u64, sender: &Addr) -> Result<Stream> {
+    let stream = STREAMS.load(deps.storage, id)?;
+
+    if *sender != stream.payer {
+        return Err(Error::NotStreamPayer);
+    }
+
+    Ok(stream)
+}
+
+//--------------------------------------------------------------------------------------------------
+// Queries
+//--------------------------------------------------------------------------------------------------
+
+pub fn query_stream(deps: Deps, time: u64, id: u64) -> Result<StreamResponse> {
+    let stream = STREAMS.load(deps.storage, id)?;
+    Ok(compute_stream_response(time, id, stream))
+}
+
+pub fn query_streams(
+    deps: Deps,
+    time: u64,
+    start_after: Option<u64>,
+    start_before: Option<u64>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> Result<Paginated<StreamResponse, u64>> {
+    let (min, max) = pagination::bounds(start_after, start_before);
+
+    let total = STREAMS.keys_raw(deps.storage, None, None, Order::Ascending).count();
+
+    let streams =
+        STREAMS.range(deps.storage, min, max, order.unwrap_or_default().into()).map(|res| {
+            let (id, stream) = res?;
+            Ok(compute_stream_response(time, id, stream))
+        });
+
+    pagination::paginate(streams, pagination::limit(limit), total as u64, |res| res.id)
+        .map_err(Into::into)
+}
+
+fn compute_stream_response(time: u64, id: u64, mut stream: Stream) -> StreamResponse {
+    stream.accrue(time);
+
+    StreamResponse {
+        id,
+        payer: stream.payer.into(),
+        recipient: stream.recipient.into(),
+        rate: stream.rate,
+        remaining: stream.remaining,
+        withdrawable: stream.accrued,
+        paused: stream.paused,
+    }
+}
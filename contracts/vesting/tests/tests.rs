@@ -1,30 +1,41 @@
+use std::iter::zip;
+
 use cosmwasm_std::{
     attr, coin, coins, from_json,
     testing::{
         mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info, MockApi,
-        MockQuerier, MockStorage,
+        MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
     },
-    Addr, BankMsg, Coin, CosmosMsg, Deps, Empty, Env, OwnedDeps, SubMsg, Timestamp, Uint128,
+    to_json_binary, Addr, BankMsg, Coin, ContractResult, CosmosMsg, Decimal, Deps, Empty, Env,
+    OwnedDeps, SubMsg, SystemResult, Timestamp, Uint128, WasmMsg, WasmQuery,
 };
 use cw2::{set_contract_version, ContractVersion, VersionError};
+use cw_storage_plus::Map;
 use cw_utils::PaymentError;
 use mars_vesting::{
     contract::{execute, instantiate, migrate, query},
+    cw721::{Cw721ExecuteMsg, Cw721QueryMsg, OwnerOfResponse},
     error::Error,
     msg::{
-        Config, ExecuteMsg, MigrateMsg, Position, PositionAlteration, PositionResponse, QueryMsg,
-        Schedule, V1_1_1Updates, V1_1_2Updates, VotingPowerResponse,
+        Config, Curve, CurvePoint, EmergencyRelease, EmergencyState, ExecuteMsg, MigrateMsg,
+        OrderBy, Paginated, PendingConfig, Position, PositionAlteration, PositionFilter,
+        PositionResponse, PositionStatus, QueryMsg, Role, RolesResponse, Schedule,
+        SolvencyResponse, StreamResponse, TimeUnit, V1_1_1Updates, V1_1_2Updates,
+        VotingPowerResponse, WithdrawAuthorizationResponse,
     },
     state::POSITIONS,
 };
 
 pub const MOCK_DENOM: &str = "umars";
 
+pub const MOCK_TIMELOCK: u64 = 604800; // one week
+
 fn mock_unlock_schedule() -> Schedule {
     Schedule {
         start_time: 1662033600, // 2022-09-01
         cliff: 0,
         duration: 63072000, // two years (365 * 24 * 60 * 60 * 2)
+        ..Default::default()
     }
 }
 
@@ -34,6 +45,13 @@ fn mock_env_at_timestamp(seconds: u64) -> Env {
     env
 }
 
+fn mock_env_at_height(height: u64) -> Env {
+    // after unlocking is complete, so that only the vesting schedule matters
+    let mut env = mock_env_at_timestamp(1759320000);
+    env.block.height = height;
+    env
+}
+
 fn query_helper<T: serde::de::DeserializeOwned>(deps: Deps, env: Env, msg: QueryMsg) -> T {
     from_json(query(deps, env, msg).unwrap()).unwrap()
 }
@@ -49,6 +67,9 @@ fn setup_test(contract_balance: &[Coin]) -> OwnedDeps<MockStorage, MockApi, Mock
             owner: "owner".to_string(),
             denom: MOCK_DENOM.into(),
             unlock_schedule: mock_unlock_schedule(),
+            timelock: MOCK_TIMELOCK,
+            emergency_release: EmergencyRelease::VestedAndUnlocked,
+            nft_contract: None,
         },
     )
     .unwrap();
@@ -67,6 +88,9 @@ fn proper_instantiation() {
             owner: "owner".to_string(),
             denom: MOCK_DENOM.into(),
             unlock_schedule: mock_unlock_schedule(),
+            timelock: MOCK_TIMELOCK,
+            emergency_release: EmergencyRelease::VestedAndUnlocked,
+            nft_contract: None,
         },
     );
 }
@@ -79,34 +103,407 @@ fn updating_ownership() {
         owner: "new_owner".into(),
         denom: MOCK_DENOM.into(),
         unlock_schedule: mock_unlock_schedule(),
+        timelock: MOCK_TIMELOCK,
+        emergency_release: EmergencyRelease::VestedAndUnlocked,
+        nft_contract: None,
     };
 
-    // non-owner cannot transfer ownership
+    // non-owner cannot propose a transfer
     let err = execute(
         deps.as_mut(),
         mock_env(),
         mock_info("non_owner", &[]),
-        ExecuteMsg::UpdateConfig {
+        ExecuteMsg::ProposeConfig {
             new_cfg: new_cfg.clone(),
         },
     )
     .unwrap_err();
-    assert_eq!(err, Error::NotOwner);
+    assert_eq!(err, Error::MissingRole(Role::Admin));
 
     // owner can propose a transfer
     let res = execute(
         deps.as_mut(),
-        mock_env(),
+        mock_env_at_timestamp(1664625600),
         mock_info("owner", &[]),
-        ExecuteMsg::UpdateConfig {
-            new_cfg,
+        ExecuteMsg::ProposeConfig {
+            new_cfg: new_cfg.clone(),
         },
     )
     .unwrap();
     assert_eq!(res.messages.len(), 0);
 
+    // the change is queued, but not yet applied
+    let pending: Option<PendingConfig<String>> =
+        query_helper(deps.as_ref(), mock_env(), QueryMsg::PendingConfig {});
+    assert_eq!(
+        pending,
+        Some(PendingConfig {
+            new_cfg: new_cfg.clone(),
+            executable_at: 1664625600 + MOCK_TIMELOCK,
+        }),
+    );
+
+    let config: Config<String> = query_helper(deps.as_ref(), mock_env(), QueryMsg::Config {});
+    assert_eq!(config.owner, "owner".to_string());
+
+    // cannot execute before the timelock has elapsed
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600 + MOCK_TIMELOCK - 1),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        Error::TimelockNotElapsed {
+            executable_at: 1664625600 + MOCK_TIMELOCK,
+            current_time: 1664625600 + MOCK_TIMELOCK - 1,
+        },
+    );
+
+    // anyone can execute once the timelock has elapsed
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600 + MOCK_TIMELOCK),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap();
+
     let config: Config<String> = query_helper(deps.as_ref(), mock_env(), QueryMsg::Config {});
     assert_eq!(config.owner, "new_owner".to_string());
+
+    let pending: Option<PendingConfig<String>> =
+        query_helper(deps.as_ref(), mock_env(), QueryMsg::PendingConfig {});
+    assert_eq!(pending, None);
+
+    // the change can't be executed twice
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600 + MOCK_TIMELOCK),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NoPendingConfig);
+}
+
+#[test]
+fn cancelling_config_change() {
+    let mut deps = setup_test(&[]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::ProposeConfig {
+            new_cfg: Config {
+                owner: "owner".into(),
+                denom: MOCK_DENOM.into(),
+                unlock_schedule: Schedule {
+                    start_time: 1662033600,
+                    cliff: 0,
+                    duration: 1,
+                    ..Default::default()
+                },
+                timelock: MOCK_TIMELOCK,
+                emergency_release: EmergencyRelease::VestedAndUnlocked,
+                nft_contract: None,
+            },
+        },
+    )
+    .unwrap();
+
+    // non-owner cannot cancel
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("non_owner", &[]),
+        ExecuteMsg::CancelConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Admin));
+
+    // owner cancels
+    execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), ExecuteMsg::CancelConfig {})
+        .unwrap();
+
+    let pending: Option<PendingConfig<String>> =
+        query_helper(deps.as_ref(), mock_env(), QueryMsg::PendingConfig {});
+    assert_eq!(pending, None);
+
+    // the cancelled change can no longer be executed
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(mock_env().block.time.seconds() + MOCK_TIMELOCK),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NoPendingConfig);
+
+    let config: Config<String> = query_helper(deps.as_ref(), mock_env(), QueryMsg::Config {});
+    assert_eq!(config.unlock_schedule, mock_unlock_schedule());
+}
+
+#[test]
+fn changing_denom() {
+    let mut deps = setup_test(&[]);
+
+    let new_cfg = Config {
+        owner: "owner".into(),
+        denom: "uosmo".into(),
+        unlock_schedule: mock_unlock_schedule(),
+        timelock: MOCK_TIMELOCK,
+        emergency_release: EmergencyRelease::VestedAndUnlocked,
+        nft_contract: None,
+    };
+
+    // the denom can be changed while there are no positions
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::ProposeConfig {
+            new_cfg: new_cfg.clone(),
+        },
+    )
+    .unwrap();
+
+    // a position is created before the change is executed
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: mock_unlock_schedule(),
+        },
+    )
+    .unwrap();
+
+    // the queued change can no longer be executed
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(mock_env().block.time.seconds() + MOCK_TIMELOCK),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        Error::DenomLocked {
+            current: MOCK_DENOM.into(),
+            new: "uosmo".into(),
+        },
+    );
+
+    // nor can a new one be proposed
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::ProposeConfig {
+            new_cfg,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        Error::DenomLocked {
+            current: MOCK_DENOM.into(),
+            new: "uosmo".into(),
+        },
+    );
+
+    let config: Config<String> = query_helper(deps.as_ref(), mock_env(), QueryMsg::Config {});
+    assert_eq!(config.denom, MOCK_DENOM.to_string());
+}
+
+#[test]
+fn granting_and_revoking_roles() {
+    let mut deps = setup_test(&[]);
+
+    let create_msg = ExecuteMsg::CreatePosition {
+        user: "larry".to_string(),
+        vest_schedule: Schedule {
+            start_time: 1614600000, // 2021-03-01
+            cliff: 31536000,        // 1 year
+            duration: 94608000,     // 3 years
+            ..Default::default()
+        },
+    };
+
+    // non-admin cannot grant roles
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("payroll", &[]),
+        ExecuteMsg::GrantRole {
+            user: "payroll".to_string(),
+            role: Role::Creator,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Admin));
+
+    // owner grants the creator role to the payroll key
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::GrantRole {
+            user: "payroll".to_string(),
+            role: Role::Creator,
+        },
+    )
+    .unwrap();
+
+    // owner grants the pauser role to the guardian
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::GrantRole {
+            user: "guardian".to_string(),
+            role: Role::Pauser,
+        },
+    )
+    .unwrap();
+
+    let res: Paginated<RolesResponse> = query_helper(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Roles {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(
+        res.data,
+        vec![
+            RolesResponse {
+                user: "guardian".to_string(),
+                roles: vec![Role::Pauser],
+            },
+            RolesResponse {
+                user: "payroll".to_string(),
+                roles: vec![Role::Creator],
+            },
+        ],
+    );
+
+    // the payroll key can create positions
+    execute(deps.as_mut(), mock_env(), mock_info("payroll", &[coin(12345, "umars")]), create_msg)
+        .unwrap();
+
+    // ...but it cannot terminate them
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("payroll", &[]),
+        ExecuteMsg::TerminatePosition {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Terminator));
+
+    // ...nor update the config
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("payroll", &[]),
+        ExecuteMsg::ProposeConfig {
+            new_cfg: Config {
+                owner: "payroll".to_string(),
+                denom: MOCK_DENOM.into(),
+                unlock_schedule: mock_unlock_schedule(),
+                timelock: 0,
+                emergency_release: EmergencyRelease::VestedAndUnlocked,
+                nft_contract: None,
+            },
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Admin));
+
+    // ...nor disable withdrawals
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("payroll", &[]),
+        ExecuteMsg::SetWithdrawEnabled {
+            enabled: false,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Pauser));
+
+    // the guardian can disable withdrawals
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("guardian", &[]),
+        ExecuteMsg::SetWithdrawEnabled {
+            enabled: false,
+        },
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::WithdrawDisabled);
+
+    // owner revokes the creator role
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::RevokeRole {
+            user: "payroll".to_string(),
+            role: Role::Creator,
+        },
+    )
+    .unwrap();
+
+    let res: Paginated<RolesResponse> = query_helper(
+        deps.as_ref(),
+        mock_env(),
+        QueryMsg::Roles {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(
+        res.data,
+        vec![RolesResponse {
+            user: "guardian".to_string(),
+            roles: vec![Role::Pauser],
+        }],
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("payroll", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "jake".to_string(),
+            vest_schedule: mock_unlock_schedule(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Creator));
 }
 
 #[test]
@@ -119,13 +516,14 @@ fn creating_positions() {
             start_time: 1614600000, // 2021-03-01
             cliff: 31536000,        // 1 year
             duration: 94608000,     // 3 years
+            ..Default::default()
         },
     };
 
     // non-owner cannot create positions
     let err =
         execute(deps.as_mut(), mock_env(), mock_info("non_owner", &[]), msg.clone()).unwrap_err();
-    assert_eq!(err, Error::NotOwner);
+    assert_eq!(err, Error::MissingRole(Role::Creator));
 
     // cannot create a position without sending a coin
     let err = execute(deps.as_mut(), mock_env(), mock_info("owner", &[]), msg.clone()).unwrap_err();
@@ -166,7 +564,10 @@ fn creating_positions() {
                 start_time: 1614600000,
                 cliff: 31536000,
                 duration: 94608000,
-            }
+                ..Default::default()
+            },
+            terminated: false,
+            held_back: Uint128::zero(),
         },
     );
 }
@@ -185,6 +586,7 @@ fn terminating_positions() {
                 start_time: 1614600000, // 2021-03-01
                 cliff: 31536000,        // 1 year
                 duration: 126144000,    // 4 years
+                ..Default::default()
             },
         },
     )
@@ -217,7 +619,7 @@ fn terminating_positions() {
     // non-owner can't terminate allocation
     let err =
         execute(deps.as_mut(), env.clone(), mock_info("non_owner", &[]), msg.clone()).unwrap_err();
-    assert_eq!(err, Error::NotOwner);
+    assert_eq!(err, Error::MissingRole(Role::Terminator));
 
     // owner properly terminates position
     let res = execute(deps.as_mut(), env, mock_info("owner", &[]), msg).unwrap();
@@ -240,8 +642,11 @@ fn terminating_positions() {
             vest_schedule: Schedule {
                 start_time: 1614600000,
                 cliff: 31536000,
-                duration: 81561600
-            }
+                duration: 81561600,
+                ..Default::default()
+            },
+            terminated: true,
+            held_back: Uint128::zero(),
         },
     );
 
@@ -271,6 +676,7 @@ fn withdrawing() {
                 start_time: 1614600000, // 2021-03-01
                 cliff: 31536000,        // 1 year
                 duration: 126144000,    // 4 years
+                ..Default::default()
             },
         },
     )
@@ -401,7 +807,7 @@ fn withdrawing() {
 }
 
 #[test]
-fn querying_positions() {
+fn withdrawing_on_behalf() {
     let mut deps = setup_test(&[]);
 
     execute(
@@ -414,178 +820,2248 @@ fn querying_positions() {
                 start_time: 1614600000, // 2021-03-01
                 cliff: 31536000,        // 1 year
                 duration: 126144000,    // 4 years
+                ..Default::default()
             },
         },
     )
     .unwrap();
 
+    // an unauthorized operator cannot withdraw
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("bot", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotAuthorized);
+
+    // larry authorizes a bot to withdraw up to 1000 umars to himself, and a custodian to withdraw
+    // an unlimited amount to a vault until 2024-01-01
     execute(
         deps.as_mut(),
         mock_env(),
-        mock_info("owner", &[coin(23456, "umars")]),
-        ExecuteMsg::CreatePosition {
-            user: "jake".to_string(),
-            vest_schedule: Schedule {
-                start_time: 612964800, // 1989-06-04
-                cliff: 0,
-                duration: 1040688000, // 33 years
-            },
+        mock_info("larry", &[]),
+        ExecuteMsg::GrantWithdrawAuthorization {
+            operator: "bot".to_string(),
+            expiry: None,
+            max_amount: Some(Uint128::new(1000)),
+            recipient: None,
         },
     )
     .unwrap();
 
-    // larry withdraws some - 507 umars
     execute(
         deps.as_mut(),
-        mock_env_at_timestamp(1664625600),
+        mock_env(),
         mock_info("larry", &[]),
-        ExecuteMsg::Withdraw {},
+        ExecuteMsg::GrantWithdrawAuthorization {
+            operator: "custodian".to_string(),
+            expiry: Some(1704067200),
+            max_amount: None,
+            recipient: Some("vault".to_string()),
+        },
     )
     .unwrap();
 
+    let res: Paginated<WithdrawAuthorizationResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1664625600),
+        QueryMsg::WithdrawAuthorizations {
+            user: "larry".to_string(),
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(
+        res.data,
+        vec![
+            WithdrawAuthorizationResponse {
+                operator: "bot".to_string(),
+                expiry: None,
+                max_amount: Some(Uint128::new(1000)),
+                recipient: None,
+            },
+            WithdrawAuthorizationResponse {
+                operator: "custodian".to_string(),
+                expiry: Some(1704067200),
+                max_amount: None,
+                recipient: Some("vault".to_string()),
+            },
+        ],
+    );
+
+    // 2022-10-01
+    // withdrawable: 507
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("bot", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "larry".to_string(),
+            amount: coins(507, MOCK_DENOM),
+        })],
+    );
+
     // 2023-10-01
-    //
-    // larry
-    // vested:       12345 * (1696161600 - 1614600000) / 126144000 = 7981
-    // unlocked:     12345 * (1696161600 - 1662033600) / 63072000  = 6679
-    // withdrawable: min(7981, 6679) - 507 = 6172
-    //
-    // jake
-    // vested:       23456 (vesting finished)
-    // unlocked:     23456 * (1696161600 - 1662033600) / 63072000 = 12691
-    // withdrawable: min(23456, 12691) - 0 = 12691
-    let expected_larry = PositionResponse {
-        user: "larry".to_string(),
-        total: Uint128::new(12345),
-        vested: Uint128::new(7981),
-        unlocked: Uint128::new(6679),
-        withdrawn: Uint128::new(507),
-        withdrawable: Uint128::new(6172),
-        vest_schedule: Schedule {
-            start_time: 1614600000, // 2021-03-01
-            cliff: 31536000,        // 1 year
-            duration: 126144000,    // 4 years
+    // withdrawable: 6172, but the bot's remaining allowance is 1000 - 507 = 493
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("bot", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "larry".to_string(),
+            amount: coins(493, MOCK_DENOM),
+        })],
+    );
+
+    // the bot's allowance is used up
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("bot", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotAuthorized);
+
+    // the custodian withdraws the rest to the vault
+    // withdrawable: 6172 - 493 = 5679
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("custodian", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "vault".to_string(),
+            amount: coins(5679, MOCK_DENOM),
+        })],
+    );
+
+    // the custodian's authorization has expired by 2024-10-01
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("custodian", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotAuthorized);
+
+    let res: Paginated<WithdrawAuthorizationResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1727784000),
+        QueryMsg::WithdrawAuthorizations {
+            user: "larry".to_string(),
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert!(res.data.is_empty());
+
+    // larry can revoke an authorization
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("larry", &[]),
+        ExecuteMsg::RevokeWithdrawAuthorization {
+            operator: "custodian".to_string(),
+        },
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161601),
+        mock_info("custodian", &[]),
+        ExecuteMsg::WithdrawOnBehalf {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotAuthorized);
+}
+
+#[test]
+fn block_height_schedules() {
+    let mut deps = setup_test(&[]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(10000, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1000,
+                cliff: 100,
+                duration: 1000,
+                unit: TimeUnit::Blocks,
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    // before the cliff, nothing is withdrawable, regardless of block time
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_height(1099),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::ZeroWithdrawable);
+
+    // height 1500
+    // vested:       10000 * (1500 - 1000) / 1000 = 5000
+    // unlocked:     10000 (unlocking finished)
+    // withdrawable: 5000
+    let res: PositionResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_height(1500),
+        QueryMsg::Position {
+            user: "larry".to_string(),
+        },
+    );
+    assert_eq!(res.vested, Uint128::new(5000));
+    assert_eq!(res.withdrawable, Uint128::new(5000));
+
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_height(1500),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "larry".to_string(),
+            amount: coins(5000, MOCK_DENOM),
+        })],
+    );
+
+    // terminating at height 1600
+    // vested:   10000 * (1600 - 1000) / 1000 = 6000
+    // reclaim:  10000 - 6000 = 4000
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_height(1600),
+        mock_info("owner", &[]),
+        ExecuteMsg::TerminatePosition {
+            user: "larry".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "owner".to_string(),
+            amount: coins(4000, MOCK_DENOM),
+        })],
+    );
+
+    let position = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("larry")).unwrap();
+    assert_eq!(
+        position,
+        Position {
+            total: Uint128::new(6000),
+            withdrawn: Uint128::new(5000),
+            vest_schedule: Schedule {
+                start_time: 1000,
+                cliff: 100,
+                duration: 600,
+                unit: TimeUnit::Blocks,
+                ..Default::default()
+            },
+            terminated: true,
+            held_back: Uint128::zero(),
         },
+    );
+}
+
+#[test]
+fn non_linear_curves() {
+    let mut deps = setup_test(&[]);
+
+    let total = 1_000_000_007u128;
+    let start_time = 1700000000;
+    let schedule = |curve: Curve| Schedule {
+        start_time,
+        cliff: 0,
+        duration: 1000,
+        curve,
+        ..Default::default()
     };
-    let expected_jake = PositionResponse {
-        user: "jake".to_string(),
-        total: Uint128::new(23456),
-        vested: Uint128::new(23456),
-        unlocked: Uint128::new(12691),
-        withdrawn: Uint128::zero(),
-        withdrawable: Uint128::new(12691),
-        vest_schedule: Schedule {
-            start_time: 612964800, // 1989-06-04
-            cliff: 0,
-            duration: 1040688000, // 33 years
+    let points = vec![
+        CurvePoint {
+            time: Decimal::percent(25),
+            amount: Decimal::percent(50),
+        },
+        CurvePoint {
+            time: Decimal::percent(50),
+            amount: Decimal::percent(60),
+        },
+    ];
+
+    // invalid curves are rejected
+    for (curve, reason) in [
+        (
+            Curve::Power {
+                exponent: 0,
+            },
+            "exponent must be greater than zero",
+        ),
+        (
+            Curve::PiecewiseLinear {
+                points: vec![],
+            },
+            "number of points must be between 1 and 10",
+        ),
+        (
+            Curve::PiecewiseLinear {
+                points: points.iter().rev().cloned().collect(),
+            },
+            "point times must be strictly increasing between 0 and 1",
+        ),
+        (
+            Curve::PiecewiseLinear {
+                points: vec![CurvePoint {
+                    time: Decimal::percent(50),
+                    amount: Decimal::percent(101),
+                }],
+            },
+            "point amounts must be non-decreasing between 0 and 1",
+        ),
+    ] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[coin(total, "umars")]),
+            ExecuteMsg::CreatePosition {
+                user: "larry".to_string(),
+                vest_schedule: schedule(curve),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            Error::InvalidSchedule {
+                reason: reason.to_string(),
+            },
+        );
+    }
+
+    let curves = [
+        (
+            "larry",
+            Curve::Power {
+                exponent: 2,
+            },
+        ),
+        (
+            "jake",
+            Curve::InversePower {
+                exponent: 2,
+            },
+        ),
+        (
+            "pumpkin",
+            Curve::PiecewiseLinear {
+                points,
+            },
+        ),
+    ];
+
+    for (user, curve) in &curves {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[coin(total, "umars")]),
+            ExecuteMsg::CreatePosition {
+                user: user.to_string(),
+                vest_schedule: schedule(curve.clone()),
+            },
+        )
+        .unwrap();
+    }
+
+    let vested_at = |deps: Deps, user: &str, elapsed: u64| -> u128 {
+        let res: PositionResponse = query_helper(
+            deps,
+            mock_env_at_timestamp(start_time + elapsed),
+            QueryMsg::Position {
+                user: user.to_string(),
+            },
+        );
+        res.vested.u128()
+    };
+
+    // power: 1000000007 * 0.5^2 = 250000001.75
+    assert_eq!(vested_at(deps.as_ref(), "larry", 500), 250000001);
+    // inverse power: 1000000007 * (1 - 0.5^2) = 750000005.25
+    assert_eq!(vested_at(deps.as_ref(), "jake", 500), 750000005);
+    // piecewise: exactly on a point, 1000000007 * 0.5 = 500000003.5
+    assert_eq!(vested_at(deps.as_ref(), "pumpkin", 250), 500000003);
+    // piecewise: within the second segment, 1000000007 * 0.55 = 550000003.85
+    assert_eq!(vested_at(deps.as_ref(), "pumpkin", 375), 550000003);
+    // piecewise: within the last segment, 1000000007 * (0.6 + 0.4 * 0.5) = 800000005.6
+    assert_eq!(vested_at(deps.as_ref(), "pumpkin", 750), 800000005);
+
+    // the vested amount never decreases, and reaches exactly the total at the end
+    for (user, _) in &curves {
+        let mut prev = 0;
+        for elapsed in (0..1000).step_by(7) {
+            let vested = vested_at(deps.as_ref(), user, elapsed);
+            assert!(vested >= prev, "{user} vested amount decreased at {elapsed}");
+            assert!(vested <= total);
+            prev = vested;
+        }
+        assert!(vested_at(deps.as_ref(), user, 999) < total);
+        assert_eq!(vested_at(deps.as_ref(), user, 1000), total);
+    }
+}
+
+#[test]
+fn explicit_cliff_amount() {
+    let mut deps = setup_test(&[]);
+
+    // 40% on cliff, remainder linearly over 3 years starting at cliff. The cliff is a quarter of the
+    // duration, so this differs from the cliff implied by a plain linear schedule.
+    let start_time = 1614600000; // 2021-03-01
+    let cliff = 31536000; // 1 year
+    let vest_schedule = Schedule {
+        start_time,
+        cliff,
+        duration: 126144000, // 4 years
+        cliff_percent: Some(Decimal::percent(40)),
+        linear_start: Some(start_time + cliff),
+        ..Default::default()
+    };
+
+    // the linear period must start within the schedule
+    for linear_start in [start_time - 1, start_time + 126144000] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[coin(10000, "umars")]),
+            ExecuteMsg::CreatePosition {
+                user: "larry".to_string(),
+                vest_schedule: Schedule {
+                    linear_start: Some(linear_start),
+                    ..vest_schedule.clone()
+                },
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            Error::InvalidSchedule {
+                reason: "linear start must be within the schedule's duration".to_string(),
+            },
+        );
+    }
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(10000, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                cliff_percent: Some(Decimal::percent(101)),
+                ..vest_schedule.clone()
+            },
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        Error::InvalidSchedule {
+            reason: "cliff percent must not exceed 1".to_string(),
+        },
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(10000, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule,
         },
+    )
+    .unwrap();
+
+    let position_at = |deps: Deps, elapsed: u64| -> PositionResponse {
+        query_helper(
+            deps,
+            mock_env_at_timestamp(start_time + elapsed),
+            QueryMsg::Position {
+                user: "larry".to_string(),
+            },
+        )
+    };
+
+    // just before the cliff, nothing is vested
+    let res = position_at(deps.as_ref(), cliff - 1);
+    assert_eq!(res.cliff_amount, Uint128::new(4000));
+    assert_eq!(res.vested, Uint128::zero());
+
+    // at the cliff, exactly the lump sum is vested, rather than a quarter of the total
+    let res = position_at(deps.as_ref(), cliff);
+    assert_eq!(res.vested, Uint128::new(4000));
+
+    // halfway through the linear period
+    // vested: 4000 + 6000 * 47304000 / 94608000 = 7000
+    let res = position_at(deps.as_ref(), cliff + 47304000);
+    assert_eq!(res.vested, Uint128::new(7000));
+
+    // 2024-09-01, when the unlock schedule completes
+    // vested:       4000 + 6000 * (1725105600 - 1646136000) / 94608000 = 9008
+    // unlocked:     10000
+    // withdrawable: 9008 (a plain linear schedule would give 8760)
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1725105600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "larry".to_string(),
+            amount: coins(9008, "umars"),
+        }))],
+    );
+
+    let res = position_at(deps.as_ref(), 1725105600 - start_time);
+    assert_eq!(res.vested, Uint128::new(9008));
+    assert_eq!(res.withdrawn, Uint128::new(9008));
+    assert_eq!(res.withdrawable, Uint128::zero());
+
+    // at the end, the total is vested
+    let res = position_at(deps.as_ref(), 126144000);
+    assert_eq!(res.vested, Uint128::new(10000));
+    assert_eq!(res.withdrawable, Uint128::new(992));
+}
+
+#[test]
+fn splitting_and_merging_positions() {
+    let mut deps = setup_test(&[]);
+
+    let vest_schedule = Schedule {
+        start_time: 1614600000, // 2021-03-01
+        cliff: 31536000,        // 1 year
+        duration: 126144000,    // 4 years
+        ..Default::default()
+    };
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: vest_schedule.clone(),
+        },
+    )
+    .unwrap();
+
+    // larry withdraws 507 umars
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+
+    // cannot split off zero, or the entire position
+    for amount in [0, 12345] {
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("larry", &[]),
+            ExecuteMsg::SplitPosition {
+                amount: Uint128::new(amount),
+                new_user: "trust".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, Error::InvalidSplitAmount);
+    }
+
+    // larry moves 4000 umars to a trust
+    // withdrawn moved: ceil(507 * 4000 / 12345) = 165
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("larry", &[]),
+        ExecuteMsg::SplitPosition {
+            amount: Uint128::new(4000),
+            new_user: "trust".to_string(),
+        },
+    )
+    .unwrap();
+
+    let larry = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("larry")).unwrap();
+    assert_eq!(
+        larry,
+        Position {
+            total: Uint128::new(8345),
+            withdrawn: Uint128::new(342),
+            vest_schedule: vest_schedule.clone(),
+            terminated: false,
+            held_back: Uint128::zero(),
+        },
+    );
+
+    let trust = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("trust")).unwrap();
+    assert_eq!(
+        trust,
+        Position {
+            total: Uint128::new(4000),
+            withdrawn: Uint128::new(165),
+            vest_schedule: vest_schedule.clone(),
+            terminated: false,
+            held_back: Uint128::one(),
+        },
+    );
+
+    // cannot split into an existing position
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("larry", &[]),
+        ExecuteMsg::SplitPosition {
+            amount: Uint128::new(1000),
+            new_user: "trust".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::PositionExists);
+
+    // the two positions combined never have more withdrawable than the original one, and have
+    // exactly as much once vesting and unlocking are complete
+    // 2022-10-01, 2023-10-01, 2024-10-01, 2025-10-01
+    let original_withdrawable = [0, 6172, 10569, 11838];
+    for (time, expected) in
+        zip([1664625600, 1696161600, 1727784000, 1759320000], original_withdrawable)
+    {
+        let withdrawable: Uint128 = ["larry", "trust"]
+            .into_iter()
+            .map(|user| {
+                let res: PositionResponse = query_helper(
+                    deps.as_ref(),
+                    mock_env_at_timestamp(time),
+                    QueryMsg::Position {
+                        user: user.to_string(),
+                    },
+                );
+                res.withdrawable
+            })
+            .sum();
+        assert!(withdrawable <= Uint128::new(expected));
+        if time == 1759320000 {
+            assert_eq!(withdrawable, Uint128::new(expected));
+        }
+    }
+
+    // cannot merge positions with different schedules
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(23456, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "jake".to_string(),
+            vest_schedule: mock_unlock_schedule(),
+        },
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("trust", &[]),
+        ExecuteMsg::MergePositions {
+            into: "jake".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::ScheduleMismatch);
+
+    // the trust merges its position back into larry's, restoring the original totals; what both
+    // positions held back is still held back, along with the allowance for merging
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("trust", &[]),
+        ExecuteMsg::MergePositions {
+            into: "larry".to_string(),
+        },
+    )
+    .unwrap();
+
+    let larry = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("larry")).unwrap();
+    assert_eq!(
+        larry,
+        Position {
+            total: Uint128::new(12345),
+            withdrawn: Uint128::new(507),
+            vest_schedule,
+            terminated: false,
+            held_back: Uint128::new(4),
+        },
+    );
+
+    assert!(!POSITIONS.has(deps.as_ref().storage, &Addr::unchecked("trust")));
+}
+
+#[test]
+fn terminating_split_positions() {
+    let mut deps = setup_test(&[]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(1000, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1662033600, // 2022-09-01
+                cliff: 0,
+                duration: 126144000, // 4 years
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    // 2024-09-01, halfway through vesting, and unlocking is finished
+    // withdrawable: 1000 * 0.5 = 500
+    let env = mock_env_at_timestamp(1725105600);
+    execute(deps.as_mut(), env.clone(), mock_info("larry", &[]), ExecuteMsg::Withdraw {}).unwrap();
+
+    // the withdrawn amount moved is rounded up: 500 * 333 / 1000 = 167, while only
+    // 333 * 0.5 = 166 of the new position is vested
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("larry", &[]),
+        ExecuteMsg::SplitPosition {
+            amount: Uint128::new(333),
+            new_user: "trust".to_string(),
+        },
+    )
+    .unwrap();
+
+    // terminating it keeps what was already withdrawn
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("owner", &[]),
+        ExecuteMsg::TerminatePosition {
+            user: "trust".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "owner".to_string(),
+            amount: coins(166, MOCK_DENOM),
+        })],
+    );
+
+    let position = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("trust")).unwrap();
+    assert_eq!(position.total, Uint128::new(167));
+    assert_eq!(position.withdrawn, Uint128::new(167));
+
+    let res: VotingPowerResponse = query_helper(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::VotingPower {
+            user: "trust".to_string(),
+        },
+    );
+    assert_eq!(res.voting_power, Uint128::zero());
+
+    let res: Paginated<VotingPowerResponse> = query_helper(
+        deps.as_ref(),
+        env.clone(),
+        QueryMsg::VotingPowers {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(res.data.len(), 2);
+
+    // 1000 - 166 reclaimed - 500 withdrawn
+    let res: SolvencyResponse = query_helper(deps.as_ref(), env, QueryMsg::Solvency {});
+    assert_eq!(res.liabilities, Uint128::new(334));
+}
+
+#[test]
+fn merging_positions_with_uneven_totals() {
+    let mut deps = setup_test(&[]);
+
+    let vest_schedule = Schedule {
+        start_time: 1614600000, // 2021-03-01
+        cliff: 0,
+        duration: 126144000, // 4 years
+        ..Default::default()
+    };
+
+    for (user, total) in [("larry", 5), ("jake", 7)] {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[coin(total, "umars")]),
+            ExecuteMsg::CreatePosition {
+                user: user.to_string(),
+                vest_schedule: vest_schedule.clone(),
+            },
+        )
+        .unwrap();
+    }
+
+    let query_position = |deps: Deps, user: &str, time: u64| -> PositionResponse {
+        query_helper(
+            deps,
+            mock_env_at_timestamp(time),
+            QueryMsg::Position {
+                user: user.to_string(),
+            },
+        )
+    };
+
+    // every tenth of the vesting duration, until well after vesting and unlocking end
+    let times = (0..=14).map(|i| 1614600000 + i * 12614400).collect::<Vec<_>>();
+    let separate = times
+        .iter()
+        .map(|&time| {
+            let larry = query_position(deps.as_ref(), "larry", time);
+            let jake = query_position(deps.as_ref(), "jake", time);
+            (larry.vested + jake.vested, larry.withdrawable + jake.withdrawable)
+        })
+        .collect::<Vec<_>>();
+
+    // 2023-03-01, halfway through vesting
+    // vested: 5 * 0.5 = 2 and 7 * 0.5 = 3
+    assert_eq!(separate[5].0, Uint128::new(5));
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("larry", &[]),
+        ExecuteMsg::MergePositions {
+            into: "jake".to_string(),
+        },
+    )
+    .unwrap();
+
+    // rounding down once rather than twice would vest 12 * 0.5 = 6; the amount held back makes up
+    // for it, so the merged position never vests nor releases more than the two separately
+    for (&time, (vested, withdrawable)) in zip(&times, separate) {
+        let merged = query_position(deps.as_ref(), "jake", time);
+        assert!(merged.vested <= vested);
+        assert!(merged.withdrawable <= withdrawable);
+    }
+
+    let merged = query_position(deps.as_ref(), "jake", times[5]);
+    assert_eq!(merged.vested, Uint128::new(3));
+
+    // everything is released once vesting and unlocking are complete
+    let merged = query_position(deps.as_ref(), "jake", times[14]);
+    assert_eq!(merged.vested, Uint128::new(12));
+    assert_eq!(merged.withdrawable, Uint128::new(12));
+}
+
+#[test]
+fn distributing() {
+    let mut deps = setup_test(&[]);
+
+    for user in ["jake", "larry", "pumpkin"] {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[coin(12345, "umars")]),
+            ExecuteMsg::CreatePosition {
+                user: user.to_string(),
+                vest_schedule: Schedule {
+                    start_time: 1614600000, // 2021-03-01
+                    cliff: 31536000,        // 1 year
+                    duration: 126144000,    // 4 years
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+    }
+
+    // jake and pumpkin opt in to auto-distribution
+    for user in ["jake", "pumpkin"] {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(user, &[]),
+            ExecuteMsg::SetAutoDistribute {
+                enabled: true,
+            },
+        )
+        .unwrap();
+    }
+
+    // an empty batch is rejected, as it would reset the sweep
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Distribute {
+            start_after: None,
+            limit: Some(0),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::ZeroLimit);
+
+    // 2022-10-01
+    // withdrawable: 507 for each position
+    //
+    // a batch starting after an explicit position pays out, but leaves the sweep where it is
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Distribute {
+            start_after: Some("larry".to_string()),
+            limit: Some(1),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "pumpkin".to_string(),
+            amount: coins(507, MOCK_DENOM),
+        })],
+    );
+
+    // the first batch of the sweep covers jake and larry; only jake opted in
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Distribute {
+            start_after: None,
+            limit: Some(2),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "jake".to_string(),
+            amount: coins(507, MOCK_DENOM),
+        })],
+    );
+
+    // the second batch resumes after larry; pumpkin has already been paid
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Distribute {
+            start_after: None,
+            limit: Some(2),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    let position = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("pumpkin")).unwrap();
+    assert_eq!(position.withdrawn, Uint128::new(507));
+
+    // the sweep is complete, so the next one starts over; nothing is withdrawable in this block
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Distribute {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    // pumpkin opts out
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("pumpkin", &[]),
+        ExecuteMsg::SetAutoDistribute {
+            enabled: false,
+        },
+    )
+    .unwrap();
+
+    // 2023-10-01
+    // withdrawable: 6679 - 507 = 6172
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("keeper", &[]),
+        ExecuteMsg::Distribute {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "jake".to_string(),
+            amount: coins(6172, MOCK_DENOM),
+        })],
+    );
+}
+
+#[test]
+fn withdrawing_tokenized_positions() {
+    let mut deps = setup_test(&[]);
+
+    // enable tokenization
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::ProposeConfig {
+            new_cfg: Config {
+                owner: "owner".to_string(),
+                denom: MOCK_DENOM.into(),
+                unlock_schedule: mock_unlock_schedule(),
+                timelock: MOCK_TIMELOCK,
+                emergency_release: EmergencyRelease::VestedAndUnlocked,
+                nft_contract: Some("nft".to_string()),
+            },
+        },
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(mock_env().block.time.seconds() + MOCK_TIMELOCK),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap();
+
+    // creating a position mints an NFT to the user
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1614600000, // 2021-03-01
+                cliff: 31536000,        // 1 year
+                duration: 126144000,    // 4 years
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "nft".to_string(),
+            msg: to_json_binary(&Cw721ExecuteMsg::Mint {
+                token_id: "larry".to_string(),
+                owner: "larry".to_string(),
+                token_uri: None,
+                extension: None,
+            })
+            .unwrap(),
+            funds: vec![],
+        })],
+    );
+
+    // larry sells the NFT to jake
+    deps.querier.update_wasm(|query| match query {
+        WasmQuery::Smart {
+            contract_addr,
+            msg,
+        } if contract_addr == "nft" => {
+            let Cw721QueryMsg::OwnerOf {
+                token_id,
+                ..
+            } = from_json(msg).unwrap();
+            assert_eq!(token_id, "larry");
+            SystemResult::Ok(ContractResult::Ok(
+                to_json_binary(&OwnerOfResponse {
+                    owner: "jake".to_string(),
+                    approvals: vec![],
+                })
+                .unwrap(),
+            ))
+        }
+        _ => panic!("unexpected query"),
+    });
+
+    // larry can no longer withdraw
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::PositionTokenized);
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::WithdrawToken {
+            token_id: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotTokenOwner);
+
+    // jake, as the NFT owner, withdraws
+    // 2022-10-01
+    // withdrawable: 507
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("jake", &[]),
+        ExecuteMsg::WithdrawToken {
+            token_id: "larry".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "jake".to_string(),
+            amount: coins(507, MOCK_DENOM),
+        })],
+    );
+
+    let position = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("larry")).unwrap();
+    assert_eq!(position.withdrawn, Uint128::new(507));
+
+    // tokenized positions can't be split
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("larry", &[]),
+        ExecuteMsg::SplitPosition {
+            amount: Uint128::new(1000),
+            new_user: "trust".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::PositionTokenized);
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("owner", &[]),
+        ExecuteMsg::EmergencyShutdown {
+            recovery_addr: "recovery".to_string(),
+        },
+    )
+    .unwrap();
+
+    // following a shutdown, only the NFT owner can withdraw as well
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("larry", &[]),
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::PositionTokenized);
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("larry", &[]),
+        ExecuteMsg::EmergencyWithdrawToken {
+            token_id: "larry".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotTokenOwner);
+
+    // 2024-10-01
+    // vested:       7981
+    // unlocked:     7981 (unlocking finished)
+    // withdrawable: min(7981, 7981) - 507 = 7474
+    // unvested:     12345 - 7981 = 4364
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("jake", &[]),
+        ExecuteMsg::EmergencyWithdrawToken {
+            token_id: "larry".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "jake".to_string(),
+                amount: coins(7474, MOCK_DENOM),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "recovery".to_string(),
+                amount: coins(4364, MOCK_DENOM),
+            }),
+        ],
+    );
+}
+
+#[test]
+fn querying_positions() {
+    let mut deps = setup_test(&[]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1614600000, // 2021-03-01
+                cliff: 31536000,        // 1 year
+                duration: 126144000,    // 4 years
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(23456, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "jake".to_string(),
+            vest_schedule: Schedule {
+                start_time: 612964800, // 1989-06-04
+                cliff: 0,
+                duration: 1040688000, // 33 years
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    // larry withdraws some - 507 umars
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+
+    // 2023-10-01
+    //
+    // larry
+    // at cliff:     12345 * 31536000 / 126144000 = 3086
+    // vested:       12345 * (1696161600 - 1614600000) / 126144000 = 7981
+    // unlocked:     12345 * (1696161600 - 1662033600) / 63072000  = 6679
+    // withdrawable: min(7981, 6679) - 507 = 6172
+    //
+    // jake
+    // vested:       23456 (vesting finished)
+    // unlocked:     23456 * (1696161600 - 1662033600) / 63072000 = 12691
+    // withdrawable: min(23456, 12691) - 0 = 12691
+    let expected_larry = PositionResponse {
+        user: "larry".to_string(),
+        total: Uint128::new(12345),
+        cliff_amount: Uint128::new(3086),
+        vested: Uint128::new(7981),
+        unlocked: Uint128::new(6679),
+        withdrawn: Uint128::new(507),
+        withdrawable: Uint128::new(6172),
+        vest_schedule: Schedule {
+            start_time: 1614600000, // 2021-03-01
+            cliff: 31536000,        // 1 year
+            duration: 126144000,    // 4 years
+            ..Default::default()
+        },
+        status: PositionStatus::Active,
+    };
+    let expected_jake = PositionResponse {
+        user: "jake".to_string(),
+        total: Uint128::new(23456),
+        cliff_amount: Uint128::zero(),
+        vested: Uint128::new(23456),
+        unlocked: Uint128::new(12691),
+        withdrawn: Uint128::zero(),
+        withdrawable: Uint128::new(12691),
+        vest_schedule: Schedule {
+            start_time: 612964800, // 1989-06-04
+            cliff: 0,
+            duration: 1040688000, // 33 years
+            ..Default::default()
+        },
+        status: PositionStatus::FullyVested,
+    };
+
+    let res: PositionResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Position {
+            user: "larry".to_string(),
+        },
+    );
+    assert_eq!(res, expected_larry);
+
+    let res: PositionResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Position {
+            user: "jake".to_string(),
+        },
+    );
+    assert_eq!(res, expected_jake);
+
+    let res: Paginated<PositionResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Positions {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(res.data.len(), 2);
+    assert_eq!(res.data[0], expected_jake);
+    assert_eq!(res.data[1], expected_larry);
+    assert_eq!(res.next, None);
+    assert_eq!(res.total, 2);
+
+    let res: Paginated<PositionResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Positions {
+            start_after: None,
+            start_before: None,
+            limit: Some(1),
+            order: None,
+        },
+    );
+    assert_eq!(res.data.len(), 1);
+    assert_eq!(res.data[0], expected_jake);
+    assert_eq!(res.next, Some("jake".to_string()));
+
+    let res: Paginated<PositionResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Positions {
+            start_after: Some("jake".to_string()),
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(res.data.len(), 1);
+    assert_eq!(res.data[0], expected_larry);
+
+    let res: Paginated<PositionResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Positions {
+            start_after: None,
+            start_before: None,
+            limit: Some(1),
+            order: Some(OrderBy::Descending),
+        },
+    );
+    assert_eq!(res.data, vec![expected_larry.clone()]);
+    assert_eq!(res.next, Some("larry".to_string()));
+
+    let res: Paginated<PositionResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::Positions {
+            start_after: None,
+            start_before: res.next,
+            limit: Some(1),
+            order: Some(OrderBy::Descending),
+        },
+    );
+    assert_eq!(res.data, vec![expected_jake.clone()]);
+    assert_eq!(res.next, None);
+
+    // voting power
+    // larry: 12345 - 507         = 11838
+    // jake:  23456 - 0           = 23456
+    // total: 12345 + 23456 - 507 = 35294
+    let vpr: VotingPowerResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::VotingPower {
+            user: "larry".to_string(),
+        },
+    );
+    assert_eq!(vpr.voting_power, Uint128::new(11838));
+
+    let vpr: VotingPowerResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::VotingPower {
+            user: "jake".to_string(),
+        },
+    );
+    assert_eq!(vpr.voting_power, Uint128::new(23456));
+
+    // query the voting power of a user who doesn't have a vesting position; should return zero
+    let vpr: VotingPowerResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::VotingPower {
+            user: "pumpkin".to_string(),
+        },
+    );
+    assert_eq!(vpr.voting_power, Uint128::zero());
+
+    let vprs: Paginated<VotingPowerResponse> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(1696161600),
+        QueryMsg::VotingPowers {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(
+        vprs.data,
+        vec![
+            VotingPowerResponse {
+                user: "jake".to_string(),
+                voting_power: Uint128::new(23456),
+            },
+            VotingPowerResponse {
+                user: "larry".to_string(),
+                voting_power: Uint128::new(11838),
+            }
+        ],
+    );
+}
+
+#[test]
+fn solvency_and_sweeping_surplus() {
+    let mut deps = setup_test(&[]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1614600000, // 2021-03-01
+                cliff: 31536000,        // 1 year
+                duration: 126144000,    // 4 years
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    // larry withdraws 507 umars
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+
+    // the contract holds less than it owes
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(10000, MOCK_DENOM));
+
+    let res: SolvencyResponse = query_helper(deps.as_ref(), mock_env(), QueryMsg::Solvency {});
+    assert_eq!(
+        res,
+        SolvencyResponse {
+            balance: Uint128::new(10000),
+            liabilities: Uint128::new(11838),
+            surplus: Uint128::zero(),
+            deficit: Uint128::new(1838),
+        },
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::SweepSurplus {
+            recipient: "treasury".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NoSurplus);
+
+    // someone accidentally sends tokens to the contract
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(15000, MOCK_DENOM));
+
+    let res: SolvencyResponse = query_helper(deps.as_ref(), mock_env(), QueryMsg::Solvency {});
+    assert_eq!(
+        res,
+        SolvencyResponse {
+            balance: Uint128::new(15000),
+            liabilities: Uint128::new(11838),
+            surplus: Uint128::new(3162),
+            deficit: Uint128::zero(),
+        },
+    );
+
+    // non-owner cannot sweep
+    let err = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("non_owner", &[]),
+        ExecuteMsg::SweepSurplus {
+            recipient: "treasury".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Admin));
+
+    // owner sweeps only the surplus
+    let res = execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::SweepSurplus {
+            recipient: "treasury".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(3162, MOCK_DENOM),
+        })],
+    );
+}
+
+#[test]
+fn streaming_payments() {
+    let mut deps = setup_test(&[]);
+
+    // t0 = 2023-11-14
+    let t0 = 1700000000;
+
+    // only creators can open streams
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0),
+        mock_info("jake", &[coin(1000, "umars")]),
+        ExecuteMsg::CreateStream {
+            recipient: "larry".to_string(),
+            rate: Uint128::new(10),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Creator));
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0),
+        mock_info("owner", &[coin(1000, "umars")]),
+        ExecuteMsg::CreateStream {
+            recipient: "larry".to_string(),
+            rate: Uint128::zero(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::InvalidStreamRate);
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0),
+        mock_info("owner", &[coin(1000, "umars")]),
+        ExecuteMsg::CreateStream {
+            recipient: "larry".to_string(),
+            rate: Uint128::new(10),
+        },
+    )
+    .unwrap();
+
+    // only the recipient can withdraw
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 30),
+        mock_info("jake", &[]),
+        ExecuteMsg::WithdrawStream {
+            id: 1,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotStreamRecipient);
+
+    // streams can't be withdrawn from while withdrawals are disabled
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::SetWithdrawEnabled {
+            enabled: false,
+        },
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 30),
+        mock_info("larry", &[]),
+        ExecuteMsg::WithdrawStream {
+            id: 1,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::WithdrawDisabled);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::SetWithdrawEnabled {
+            enabled: true,
+        },
+    )
+    .unwrap();
+
+    // 30 seconds in, 300 umars have been streamed
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 30),
+        mock_info("larry", &[]),
+        ExecuteMsg::WithdrawStream {
+            id: 1,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "larry".to_string(),
+            amount: coins(300, MOCK_DENOM),
+        })],
+    );
+
+    // only the payer can pause the stream
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 50),
+        mock_info("larry", &[]),
+        ExecuteMsg::SetStreamPaused {
+            id: 1,
+            paused: true,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotStreamPayer);
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 50),
+        mock_info("owner", &[]),
+        ExecuteMsg::SetStreamPaused {
+            id: 1,
+            paused: true,
+        },
+    )
+    .unwrap();
+
+    // nothing is streamed while paused
+    let res: StreamResponse = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(t0 + 80),
+        QueryMsg::Stream {
+            id: 1,
+        },
+    );
+    assert_eq!(
+        res,
+        StreamResponse {
+            id: 1,
+            payer: "owner".to_string(),
+            recipient: "larry".to_string(),
+            rate: Uint128::new(10),
+            remaining: Uint128::new(500),
+            withdrawable: Uint128::new(200),
+            paused: true,
+        },
+    );
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 100),
+        mock_info("owner", &[]),
+        ExecuteMsg::SetStreamPaused {
+            id: 1,
+            paused: false,
+        },
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 100),
+        mock_info("owner", &[coin(500, "umars")]),
+        ExecuteMsg::TopUpStream {
+            id: 1,
+        },
+    )
+    .unwrap();
+
+    // 50 seconds after resuming
+    // streamed:  500 + 200 = 700
+    // remaining: 1000 - 500 = 500
+    let res: Paginated<StreamResponse, u64> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(t0 + 150),
+        QueryMsg::Streams {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert_eq!(res.data.len(), 1);
+    assert_eq!(res.data[0].remaining, Uint128::new(500));
+    assert_eq!(res.data[0].withdrawable, Uint128::new(700));
+
+    // streams count towards the contract's liabilities
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(1200, MOCK_DENOM));
+    let res: SolvencyResponse = query_helper(deps.as_ref(), mock_env(), QueryMsg::Solvency {});
+    assert_eq!(res.liabilities, Uint128::new(1200));
+    assert_eq!(res.surplus, Uint128::zero());
+
+    // the payer cancels: the streamed amount goes to the recipient, the rest is refunded
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(t0 + 150),
+        mock_info("owner", &[]),
+        ExecuteMsg::CancelStream {
+            id: 1,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "larry".to_string(),
+                amount: coins(700, MOCK_DENOM),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "owner".to_string(),
+                amount: coins(500, MOCK_DENOM),
+            }),
+        ],
+    );
+
+    let res: Paginated<StreamResponse, u64> = query_helper(
+        deps.as_ref(),
+        mock_env_at_timestamp(t0 + 150),
+        QueryMsg::Streams {
+            start_after: None,
+            start_before: None,
+            limit: None,
+            order: None,
+        },
+    );
+    assert!(res.data.is_empty());
+}
+
+#[test]
+fn querying_positions_by() {
+    let mut deps = setup_test(&[]);
+
+    for (user, start_time, duration) in [
+        ("larry", 1614600000, 126144000),
+        ("jake", 612964800, 1040688000),
+        ("pumpkin", 1650000000, 100),
+        ("trust", 1600000000, 1000),
+    ] {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("owner", &[coin(10000, "umars")]),
+            ExecuteMsg::CreatePosition {
+                user: user.to_string(),
+                vest_schedule: Schedule {
+                    start_time,
+                    cliff: 0,
+                    duration,
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+    }
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1650000050),
+        mock_info("owner", &[]),
+        ExecuteMsg::TerminatePosition {
+            user: "pumpkin".to_string(),
+        },
+    )
+    .unwrap();
+
+    // unlocking has finished by now, so trust can withdraw everything
+    let time = 1730000000;
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(time),
+        mock_info("trust", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+
+    // the cursor is where the previous page ended, in the direction of iteration
+    let query_page = |deps: Deps,
+                      filter: PositionFilter,
+                      cursor: Option<&str>,
+                      limit: Option<u32>,
+                      order: Option<OrderBy>| {
+        let cursor = cursor.map(String::from);
+        let (start_after, start_before) = match order {
+            Some(OrderBy::Descending) => (None, cursor),
+            _ => (cursor, None),
+        };
+        let res: Paginated<PositionResponse> = query_helper(
+            deps,
+            mock_env_at_timestamp(time),
+            QueryMsg::PositionsBy {
+                filter,
+                start_after,
+                start_before,
+                limit,
+                order,
+            },
+        );
+        res
+    };
+    let query_users = |deps: Deps,
+                       filter: PositionFilter,
+                       cursor: Option<&str>,
+                       limit: Option<u32>,
+                       order: Option<OrderBy>| {
+        let res = query_page(deps, filter, cursor, limit, order);
+        res.data.into_iter().map(|position| position.user).collect::<Vec<_>>()
     };
 
-    let res: PositionResponse = query_helper(
-        deps.as_ref(),
-        mock_env_at_timestamp(1696161600),
-        QueryMsg::Position {
-            user: "larry".to_string(),
-        },
+    // by start time, inclusive of both ends
+    let filter = PositionFilter::StartTime {
+        min: Some(1600000000),
+        max: Some(1650000000),
+    };
+    assert_eq!(
+        query_users(deps.as_ref(), filter.clone(), None, None, None),
+        vec!["trust", "larry", "pumpkin"],
+    );
+    assert_eq!(
+        query_users(deps.as_ref(), filter.clone(), None, None, Some(OrderBy::Descending)),
+        vec!["pumpkin", "larry", "trust"],
+    );
+
+    // paginated in both directions
+    let res = query_page(deps.as_ref(), filter.clone(), None, Some(2), None);
+    assert_eq!(res.next, Some("larry".to_string()));
+    assert_eq!(res.total, 3);
+    assert_eq!(
+        query_users(deps.as_ref(), filter.clone(), None, Some(2), None),
+        vec!["trust", "larry"],
+    );
+    assert_eq!(
+        query_users(deps.as_ref(), filter.clone(), Some("larry"), Some(2), None),
+        vec!["pumpkin"],
+    );
+    assert_eq!(
+        query_users(deps.as_ref(), filter, Some("pumpkin"), Some(1), Some(OrderBy::Descending)),
+        vec!["larry"],
+    );
+
+    // open-ended range
+    let filter = PositionFilter::StartTime {
+        min: None,
+        max: Some(1614600000),
+    };
+    assert_eq!(
+        query_users(deps.as_ref(), filter, None, None, None),
+        vec!["jake", "trust", "larry"]
     );
-    assert_eq!(res, expected_larry);
+
+    // by status
+    for (status, user) in [
+        (PositionStatus::Active, "larry"),
+        (PositionStatus::FullyVested, "jake"),
+        (PositionStatus::Terminated, "pumpkin"),
+        (PositionStatus::FullyWithdrawn, "trust"),
+    ] {
+        let filter = PositionFilter::Status {
+            status,
+        };
+        assert_eq!(query_users(deps.as_ref(), filter, None, None, None), vec![user]);
+    }
 
     let res: PositionResponse = query_helper(
         deps.as_ref(),
-        mock_env_at_timestamp(1696161600),
+        mock_env_at_timestamp(time),
         QueryMsg::Position {
-            user: "jake".to_string(),
+            user: "pumpkin".to_string(),
         },
     );
-    assert_eq!(res, expected_jake);
+    assert_eq!(res.status, PositionStatus::Terminated);
+}
 
-    let res: Vec<PositionResponse> = query_helper(
-        deps.as_ref(),
+fn setup_shutdown_test(
+    emergency_release: EmergencyRelease,
+) -> OwnedDeps<MockStorage, MockApi, MockQuerier, Empty> {
+    let mut deps = setup_test(&[]);
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[]),
+        ExecuteMsg::ProposeConfig {
+            new_cfg: Config {
+                owner: "owner".to_string(),
+                denom: MOCK_DENOM.into(),
+                unlock_schedule: mock_unlock_schedule(),
+                timelock: 0,
+                emergency_release,
+                nft_contract: None,
+            },
+        },
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(mock_env().block.time.seconds() + MOCK_TIMELOCK),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ExecuteConfig {},
+    )
+    .unwrap();
+
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "larry".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1614600000, // 2021-03-01
+                cliff: 31536000,        // 1 year
+                duration: 126144000,    // 4 years
+                ..Default::default()
+            },
+        },
+    )
+    .unwrap();
+
+    // larry withdraws 507 umars
+    execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1664625600),
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap();
+
+    deps
+}
+
+#[test]
+fn emergency_shutdown() {
+    let mut deps = setup_shutdown_test(EmergencyRelease::VestedAndUnlocked);
+
+    // cannot emergency withdraw before shutdown
+    let err = execute(
+        deps.as_mut(),
         mock_env_at_timestamp(1696161600),
-        QueryMsg::Positions {
-            start_after: None,
+        mock_info("larry", &[]),
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotShutdown);
+
+    let msg = ExecuteMsg::EmergencyShutdown {
+        recovery_addr: "recovery".to_string(),
+    };
+
+    // non-owner cannot shut down the contract
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("non_owner", &[]),
+        msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::MissingRole(Role::Admin));
+
+    // cannot recover unvested tokens before shutdown either
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("anyone", &[]),
+        ExecuteMsg::EmergencyRecover {
             limit: None,
         },
-    );
-    assert_eq!(res.len(), 2);
-    assert_eq!(res[0], expected_jake);
-    assert_eq!(res[1], expected_larry);
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::NotShutdown);
 
-    let res: Vec<PositionResponse> = query_helper(
-        deps.as_ref(),
-        mock_env_at_timestamp(1696161600),
-        QueryMsg::Positions {
-            start_after: None,
-            limit: Some(1),
+    execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("owner", &[coin(10000, "umars")]),
+        ExecuteMsg::CreatePosition {
+            user: "jake".to_string(),
+            vest_schedule: Schedule {
+                start_time: 1614600000, // 2021-03-01
+                cliff: 31536000,        // 1 year
+                duration: 126144000,    // 4 years
+                ..Default::default()
+            },
         },
+    )
+    .unwrap();
+
+    // no position is terminated by the shutdown itself
+    let res =
+        execute(deps.as_mut(), mock_env_at_timestamp(1696161600), mock_info("owner", &[]), msg)
+            .unwrap();
+    assert_eq!(res.messages, vec![]);
+
+    let state: Option<EmergencyState> =
+        query_helper(deps.as_ref(), mock_env(), QueryMsg::EmergencyState {});
+    assert_eq!(
+        state,
+        Some(EmergencyState {
+            triggered_at: 1696161600,
+            triggered_height: 12345,
+            recovery_addr: "recovery".to_string(),
+            recovered: Uint128::zero(),
+        }),
     );
-    assert_eq!(res.len(), 1);
-    assert_eq!(res[0], expected_jake);
 
-    let res: Vec<PositionResponse> = query_helper(
-        deps.as_ref(),
-        mock_env_at_timestamp(1696161600),
-        QueryMsg::Positions {
-            start_after: Some("jake".to_string()),
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::EmergencyRecover {
+            limit: Some(0),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::ZeroLimit);
+
+    // anyone can terminate the positions in batches; vesting stopped at the time of the shutdown,
+    // however late they are terminated
+    // 2023-10-01
+    // jake
+    // vested:       10000 * (1696161600 - 1614600000) / 126144000 = 6465
+    // unvested:     10000 - 6465 = 3535
+    // larry
+    // vested:       12345 * (1696161600 - 1614600000) / 126144000 = 7981
+    // unvested:     12345 - 7981 = 4364
+    for amount in [3535, 4364] {
+        let res = execute(
+            deps.as_mut(),
+            mock_env_at_timestamp(1727784000),
+            mock_info("anyone", &[]),
+            ExecuteMsg::EmergencyRecover {
+                limit: Some(1),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "recovery".to_string(),
+                amount: coins(amount, MOCK_DENOM),
+            })],
+        );
+    }
+
+    // sweeping again finds nothing left to recover
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("anyone", &[]),
+        ExecuteMsg::EmergencyRecover {
             limit: None,
         },
-    );
-    assert_eq!(res.len(), 1);
-    assert_eq!(res[0], expected_larry);
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
 
-    // voting power
-    // larry: 12345 - 507         = 11838
-    // jake:  23456 - 0           = 23456
-    // total: 12345 + 23456 - 507 = 35294
-    let vpr: VotingPowerResponse = query_helper(
-        deps.as_ref(),
+    let state: Option<EmergencyState> =
+        query_helper(deps.as_ref(), mock_env(), QueryMsg::EmergencyState {});
+    assert_eq!(state.unwrap().recovered, Uint128::new(7899));
+
+    // all other execute paths are frozen
+    let err = execute(
+        deps.as_mut(),
         mock_env_at_timestamp(1696161600),
-        QueryMsg::VotingPower {
-            user: "larry".to_string(),
-        },
-    );
-    assert_eq!(vpr.voting_power, Uint128::new(11838));
+        mock_info("larry", &[]),
+        ExecuteMsg::Withdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::Shutdown);
 
-    let vpr: VotingPowerResponse = query_helper(
-        deps.as_ref(),
+    let err = execute(
+        deps.as_mut(),
         mock_env_at_timestamp(1696161600),
-        QueryMsg::VotingPower {
+        mock_info("owner", &[coin(12345, "umars")]),
+        ExecuteMsg::CreatePosition {
             user: "jake".to_string(),
+            vest_schedule: mock_unlock_schedule(),
         },
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::Shutdown);
+
+    // vesting has stopped, but unlocking continues
+    // 2024-10-01
+    // vested:       7981
+    // unlocked:     7981 (unlocking finished)
+    // withdrawable: min(7981, 7981) - 507 = 7474
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("larry", &[]),
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "larry".to_string(),
+            amount: coins(7474, MOCK_DENOM),
+        })],
     );
-    assert_eq!(vpr.voting_power, Uint128::new(23456));
 
-    // query the voting power of a user who doesn't have a vesting position; should return zero
-    let vpr: VotingPowerResponse = query_helper(
-        deps.as_ref(),
+    let err = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1727784000),
+        mock_info("larry", &[]),
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap_err();
+    assert_eq!(err, Error::ZeroWithdrawable);
+}
+
+#[test]
+fn emergency_withdrawing_vested() {
+    let mut deps = setup_shutdown_test(EmergencyRelease::Vested);
+
+    execute(
+        deps.as_mut(),
         mock_env_at_timestamp(1696161600),
-        QueryMsg::VotingPower {
-            user: "pumpkin".to_string(),
+        mock_info("owner", &[]),
+        ExecuteMsg::EmergencyShutdown {
+            recovery_addr: "recovery".to_string(),
         },
-    );
-    assert_eq!(vpr.voting_power, Uint128::zero());
+    )
+    .unwrap();
 
-    let vprs: Vec<VotingPowerResponse> = query_helper(
-        deps.as_ref(),
+    // the full vested amount can be withdrawn right away, ignoring the unlock schedule; the
+    // position is terminated first, as it hasn't been recovered from yet
+    // withdrawable: 7981 - 507 = 7474
+    // unvested:     12345 - 7981 = 4364
+    let res = execute(
+        deps.as_mut(),
         mock_env_at_timestamp(1696161600),
-        QueryMsg::VotingPowers {
-            start_after: None,
-            limit: None,
-        },
-    );
+        mock_info("larry", &[]),
+        ExecuteMsg::EmergencyWithdraw {},
+    )
+    .unwrap();
     assert_eq!(
-        vprs,
+        res.messages,
         vec![
-            VotingPowerResponse {
-                user: "jake".to_string(),
-                voting_power: Uint128::new(23456),
-            },
-            VotingPowerResponse {
-                user: "larry".to_string(),
-                voting_power: Uint128::new(11838),
-            }
+            SubMsg::new(BankMsg::Send {
+                to_address: "larry".to_string(),
+                amount: coins(7474, MOCK_DENOM),
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "recovery".to_string(),
+                amount: coins(4364, MOCK_DENOM),
+            }),
         ],
     );
+
+    let position = POSITIONS.load(deps.as_ref().storage, &Addr::unchecked("larry")).unwrap();
+    assert_eq!(position.total, position.withdrawn);
+
+    // the position's unvested tokens are only recovered once
+    let res = execute(
+        deps.as_mut(),
+        mock_env_at_timestamp(1696161600),
+        mock_info("anyone", &[]),
+        ExecuteMsg::EmergencyRecover {
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![]);
 }
 
 #[test]
@@ -635,6 +3111,7 @@ fn proper_migration_for_v1_1_1() {
                 start_time: 1614600000, // 2021-03-01
                 cliff: 31536000,        // 1 year
                 duration: 126144000,    // 4 years
+                ..Default::default()
             },
         },
     )
@@ -650,6 +3127,7 @@ fn proper_migration_for_v1_1_1() {
                 start_time: 1614600000, // 2021-03-01
                 cliff: 31536000,        // 1 year
                 duration: 126144000,    // 4 years
+                ..Default::default()
             },
         },
     )
@@ -685,14 +3163,17 @@ fn proper_migration_for_v1_1_1() {
     assert!(res.data.is_none());
     assert_eq!(
         res.attributes,
-        vec![attr("action", "migrate"), attr("from_version", "1.1.0"), attr("to_version", "1.1.2"),] // We can't test the actual version here, as it's set in the migration, should be 1.1.1
+        vec![attr("action", "migrate"), attr("from_version", "1.1.0"), attr("to_version", "1.1.1"),]
     );
+
+    let version = cw2::get_contract_version(deps.as_ref().storage).unwrap();
+    assert_eq!(version.version, "1.1.1");
 }
 
 #[test]
 fn proper_migration_for_v1_1_2() {
     let mars_balance = coin(45235, MOCK_DENOM);
-    let mut deps = setup_test(&[mars_balance.clone()]);
+    let mut deps = setup_test(std::slice::from_ref(&mars_balance));
     cw2::set_contract_version(deps.as_mut().storage, "crates.io:mars-vesting", "1.1.1").unwrap();
 
     execute(
@@ -705,6 +3186,7 @@ fn proper_migration_for_v1_1_2() {
                 start_time: 1614600000, // 2021-03-01
                 cliff: 31536000,        // 1 year
                 duration: 126144000,    // 4 years
+                ..Default::default()
             },
         },
     )
@@ -739,4 +3221,64 @@ fn proper_migration_for_v1_1_2() {
         res.attributes,
         vec![attr("action", "migrate"), attr("from_version", "1.1.1"), attr("to_version", "1.1.2"),]
     );
+
+    let version = cw2::get_contract_version(deps.as_ref().storage).unwrap();
+    assert_eq!(version.version, "1.1.2");
+}
+
+#[test]
+fn proper_migration_for_v1_2_0() {
+    let mut deps = setup_test(&[]);
+    cw2::set_contract_version(deps.as_mut().storage, "crates.io:mars-vesting", "1.1.2").unwrap();
+
+    // positions saved before the indexes existed
+    let legacy_positions: Map<&Addr, Position> = Map::new("positions");
+    for user in ["larry", "jake"] {
+        legacy_positions
+            .save(
+                deps.as_mut().storage,
+                &Addr::unchecked(user),
+                &Position {
+                    total: Uint128::new(10000),
+                    withdrawn: Uint128::zero(),
+                    vest_schedule: Schedule {
+                        start_time: 1614600000, // 2021-03-01
+                        cliff: 31536000,        // 1 year
+                        duration: 126144000,    // 4 years
+                        ..Default::default()
+                    },
+                    terminated: false,
+                    held_back: Uint128::zero(),
+                },
+            )
+            .unwrap();
+    }
+
+    let query_msg = QueryMsg::PositionsBy {
+        filter: PositionFilter::Status {
+            status: PositionStatus::Active,
+        },
+        start_after: None,
+        start_before: None,
+        limit: None,
+        order: None,
+    };
+
+    let res: Paginated<PositionResponse> =
+        query_helper(deps.as_ref(), mock_env(), query_msg.clone());
+    assert!(res.data.is_empty());
+
+    let res = migrate(deps.as_mut(), mock_env(), MigrateMsg::V1_1_2ToV1_2_0 {}).unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            attr("action", "migrate"),
+            attr("from_version", "1.1.2"),
+            attr("to_version", "1.2.0"),
+            attr("positions_indexed", "2"),
+        ]
+    );
+
+    let res: Paginated<PositionResponse> = query_helper(deps.as_ref(), mock_env(), query_msg);
+    assert_eq!(res.data.len(), 2);
 }
@@ -0,0 +1,143 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Order, StdResult};
+use cw_storage_plus::{Bound, PrimaryKey};
+
+pub const MAX_LIMIT: u32 = 30;
+pub const DEFAULT_LIMIT: u32 = 10;
+
+#[cw_serde]
+#[derive(Copy, Default)]
+pub enum OrderBy {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+impl From<OrderBy> for Order {
+    fn from(order: OrderBy) -> Self {
+        match order {
+            OrderBy::Ascending => Order::Ascending,
+            OrderBy::Descending => Order::Descending,
+        }
+    }
+}
+
+/// A page of results of a list query
+#[cw_serde]
+pub struct Paginated<T, C = String> {
+    /// Items in this page
+    pub data: Vec<T>,
+    /// Cursor from which to request the next page; `None` if this is the last page. To be used as
+    /// `start_after` when iterating in ascending order, or as `start_before` when descending.
+    pub next: Option<C>,
+    /// Total number of items matching the query, across all pages
+    pub total: u64,
+}
+
+/// Number of items to return in a page, given the requested limit
+pub fn limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
+/// Bounds of a range over the items strictly between `start_after` and `start_before`, either of
+/// which may be omitted. The bounds are the same whichever the order of iteration.
+pub fn bounds<'a, K: PrimaryKey<'a>>(
+    start_after: Option<K>,
+    start_before: Option<K>,
+) -> (Option<Bound<'a, K>>, Option<Bound<'a, K>>) {
+    (start_after.map(Bound::exclusive), start_before.map(Bound::exclusive))
+}
+
+/// Take a page of up to `limit` items, and determine the cursor of the next page using `cursor`.
+///
+/// `items` must already be bounded and ordered; it is read at most `limit + 1` times.
+pub fn paginate<T, C>(
+    items: impl Iterator<Item = StdResult<T>>,
+    limit: usize,
+    total: u64,
+    cursor: impl Fn(&T) -> C,
+) -> StdResult<Paginated<T, C>> {
+    let mut data = items.take(limit + 1).collect::<StdResult<Vec<_>>>()?;
+
+    // an extra item means there is at least one more page
+    let next = if data.len() > limit {
+        data.pop();
+        data.last().map(cursor)
+    } else {
+        None
+    };
+
+    Ok(Paginated {
+        data,
+        next,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::MockStorage;
+    use cw_storage_plus::Map;
+
+    use super::*;
+
+    const NUMBERS: Map<u64, u64> = Map::new("numbers");
+
+    fn page(
+        start_after: Option<u64>,
+        start_before: Option<u64>,
+        limit: Option<u32>,
+        order: OrderBy,
+    ) -> Paginated<u64, u64> {
+        let mut store = MockStorage::new();
+        for n in 1..=50 {
+            NUMBERS.save(&mut store, n, &(n * 10)).unwrap();
+        }
+
+        let (min, max) = bounds(start_after, start_before);
+        let items = NUMBERS.range(&store, min, max, order.into()).map(|res| res.map(|(n, _)| n));
+        paginate(items, super::limit(limit), 50, |n| *n).unwrap()
+    }
+
+    #[test]
+    fn paginating_ascending() {
+        let res = page(None, None, Some(3), OrderBy::Ascending);
+        assert_eq!(res.data, vec![1, 2, 3]);
+        assert_eq!(res.next, Some(3));
+        assert_eq!(res.total, 50);
+
+        let res = page(res.next, None, Some(3), OrderBy::Ascending);
+        assert_eq!(res.data, vec![4, 5, 6]);
+
+        // the last page has no cursor
+        let res = page(Some(47), None, Some(3), OrderBy::Ascending);
+        assert_eq!(res.data, vec![48, 49, 50]);
+        assert_eq!(res.next, None);
+    }
+
+    #[test]
+    fn paginating_descending() {
+        let res = page(None, None, Some(3), OrderBy::Descending);
+        assert_eq!(res.data, vec![50, 49, 48]);
+        assert_eq!(res.next, Some(48));
+
+        let res = page(None, res.next, Some(3), OrderBy::Descending);
+        assert_eq!(res.data, vec![47, 46, 45]);
+    }
+
+    #[test]
+    fn paginating_between_cursors() {
+        let res = page(Some(10), Some(14), None, OrderBy::Ascending);
+        assert_eq!(res.data, vec![11, 12, 13]);
+        assert_eq!(res.next, None);
+
+        let res = page(Some(10), Some(14), None, OrderBy::Descending);
+        assert_eq!(res.data, vec![13, 12, 11]);
+    }
+
+    #[test]
+    fn limiting_page_size() {
+        assert_eq!(page(None, None, None, OrderBy::Ascending).data.len(), 10);
+        assert_eq!(page(None, None, Some(100), OrderBy::Ascending).data.len(), 30);
+    }
+}